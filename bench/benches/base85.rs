@@ -0,0 +1,52 @@
+use arrowvortex_clipboard::{base85, Note, NoteKind, Row};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Large dense chart with a mix of note kinds
+fn chart(num_notes: u64) -> Vec<Note<Row>> {
+    (0..num_notes)
+        .map(|i| Note {
+            pos: Row(i * 12),
+            column: (i % 4) as u8,
+            kind: match i % 8 {
+                0 => NoteKind::Hold {
+                    end_pos: Row(i * 12 + 48),
+                },
+                3 => NoteKind::Mine,
+                _ => NoteKind::Tap,
+            },
+        })
+        .collect()
+}
+
+fn bench_base85(c: &mut Criterion) {
+    let bytes = (0..1_000_000_u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect::<Vec<_>>();
+    let text = base85::encode(&bytes);
+
+    let mut group = c.benchmark_group("base85");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("encode", |b| b.iter(|| base85::encode(black_box(&bytes))));
+    group.bench_function("decode", |b| {
+        b.iter(|| base85::decode(black_box(text.as_bytes())))
+    });
+    group.finish();
+}
+
+fn bench_clipboard(c: &mut Criterion) {
+    let notes = chart(100_000);
+    let encoded = arrowvortex_clipboard::encode_row_based_notes(&notes).unwrap();
+
+    let mut group = c.benchmark_group("clipboard");
+    group.throughput(Throughput::Elements(notes.len() as u64));
+    group.bench_function("encode_row_based_notes", |b| {
+        b.iter(|| arrowvortex_clipboard::encode_row_based_notes(black_box(&notes)))
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| arrowvortex_clipboard::decode(black_box(encoded.as_bytes())))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_base85, bench_clipboard);
+criterion_main!(benches);
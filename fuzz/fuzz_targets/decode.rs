@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Most inputs would be rejected for lacking the signature, so add it to reach the decoder
+    for signature in &[&b"ArrowVortex:notes:"[..], b"ArrowVortex:tempo:"] {
+        let _ = arrowvortex_clipboard::decode(&[signature, data].concat());
+    }
+    let _ = arrowvortex_clipboard::decode(data);
+});
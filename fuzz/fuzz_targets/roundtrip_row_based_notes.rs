@@ -0,0 +1,12 @@
+#![no_main]
+
+use arrowvortex_clipboard::{fuzzing::SortedNotes, DecodeResult, Row};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|notes: SortedNotes<Row>| {
+    let encoded = arrowvortex_clipboard::encode_row_based_notes(&notes.0).unwrap();
+    match arrowvortex_clipboard::decode(encoded.as_bytes()).unwrap() {
+        DecodeResult::RowBasedNotes(decoded) => assert_eq!(decoded, notes.0),
+        _ => panic!("expected row-based notes"),
+    }
+});
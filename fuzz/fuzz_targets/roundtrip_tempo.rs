@@ -0,0 +1,12 @@
+#![no_main]
+
+use arrowvortex_clipboard::{fuzzing::SortedTempoEvents, DecodeResult};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|tempo_events: SortedTempoEvents| {
+    let encoded = arrowvortex_clipboard::encode_tempo(&tempo_events.0).unwrap();
+    match arrowvortex_clipboard::decode(encoded.as_bytes()).unwrap() {
+        DecodeResult::TempoEvents(decoded) => assert_eq!(decoded, tempo_events.0),
+        _ => panic!("expected tempo events"),
+    }
+});
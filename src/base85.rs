@@ -0,0 +1,188 @@
+/*!
+The base85 variant that ArrowVortex uses to turn binary clipboard data into text
+
+ArrowVortex groups bytes into big-endian 32bit ints and writes each as 5 base85 digits, starting
+from ASCII 33 (`!`). A group of four zero bytes is shortened to `z`. A trailing group of fewer
+than four bytes is padded with zeros and written with one digit more than it has bytes.
+
+The functions in this module convert whole buffers at once, using lookup tables instead of a
+division per digit. They produce the same output as the clipboard encoder and decoder, which use
+them internally, and are meant for bulk conversion of many or large payloads.
+
+```rust
+use arrowvortex_clipboard::base85;
+
+let bytes = [0xC9, 0xE8, 0xC9, 0x19, 0, 0, 0, 0, 0xDC];
+let text = base85::encode(&bytes);
+assert_eq!(text, "alphazg]");
+assert_eq!(base85::decode(text.as_bytes())?[..9], bytes);
+# Ok::<(), arrowvortex_clipboard::DecodeError>(())
+```
+*/
+
+use crate::DecodeError;
+use alloc::{string::String, vec::Vec};
+
+/// Number of values that two base85 digits can represent
+const PAIR_RANGE: u32 = 85 * 85;
+
+/// ASCII representation of every pair of base85 digits
+static DIGIT_PAIRS: [[u8; 2]; PAIR_RANGE as usize] = digit_pairs();
+
+const fn digit_pairs() -> [[u8; 2]; PAIR_RANGE as usize] {
+    let mut pairs = [[0; 2]; PAIR_RANGE as usize];
+    let mut i = 0;
+    while i < PAIR_RANGE as usize {
+        pairs[i] = [33 + (i / 85) as u8, 33 + (i % 85) as u8];
+        i += 1;
+    }
+    pairs
+}
+
+/// Marks characters in [`DIGIT_VALUES`] that can't appear in encoded data
+const INVALID: u8 = 0xFF;
+
+/// Value of every ASCII character as a base85 digit. `z` is only valid at the start of a group,
+/// where it's handled separately
+static DIGIT_VALUES: [u8; 256] = digit_values();
+
+const fn digit_values() -> [u8; 256] {
+    let mut values = [INVALID; 256];
+    let mut character = b'!';
+    while character <= b'u' {
+        values[character as usize] = character - 33;
+        character += 1;
+    }
+    values
+}
+
+/// Returns the five base85 digits of a 32bit int
+pub(crate) fn encode_dword(dword: u32) -> [u8; 5] {
+    let low = DIGIT_PAIRS[(dword % PAIR_RANGE) as usize];
+    let rest = dword / PAIR_RANGE;
+    let middle = DIGIT_PAIRS[(rest % PAIR_RANGE) as usize];
+    // Below 85, because 2^32 < 85^5
+    let high = 33 + (rest / PAIR_RANGE) as u8;
+    [high, middle[0], middle[1], low[0], low[1]]
+}
+
+/// Encodes bytes into base85 text
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = Vec::with_capacity(bytes.len() / 4 * 5 + 5);
+
+    let mut chunks = bytes.chunks_exact(4);
+    for chunk in &mut chunks {
+        let dword = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        if dword == 0 {
+            output.push(b'z');
+        } else {
+            output.extend_from_slice(&encode_dword(dword));
+        }
+    }
+
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        let mut padded = [0; 4];
+        padded[..remainder.len()].copy_from_slice(remainder);
+        let digits = encode_dword(u32::from_be_bytes(padded));
+        output.extend_from_slice(&digits[..remainder.len() + 1]);
+    }
+
+    String::from_utf8(output).expect("base85 digits are ASCII")
+}
+
+/// Decodes base85 text into bytes
+///
+/// A trailing group of fewer than five digits is padded, and always decodes into four bytes. Fails
+/// with [`DecodeError::InvalidCharacter`] on characters outside the base85 alphabet, including a
+/// `z` inside a group.
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let digit_value = |character: u8| match DIGIT_VALUES[character as usize] {
+        INVALID => Err(DecodeError::InvalidCharacter { character }),
+        value => Ok(value as u64),
+    };
+
+    let mut output = Vec::with_capacity(data.len() / 5 * 4 + 4);
+    let mut rest = data;
+    while let Some(&first_char) = rest.first() {
+        // 'z' is a shorthand for an entire zero chunk
+        if first_char == b'z' {
+            output.extend_from_slice(&[0; 4]);
+            rest = &rest[1..];
+            continue;
+        }
+
+        let (group, remaining) = rest.split_at(rest.len().min(5));
+        // Computed in 64 bits because five digits can exceed the 32bit range in corrupt input
+        let mut dword = 0;
+        for i in 0..5 {
+            let digit = match group.get(i) {
+                Some(&character) => digit_value(character)?,
+                None => 85,
+            };
+            dword = dword * 85 + digit;
+        }
+        output.extend_from_slice(&(dword as u32).to_be_bytes());
+        rest = remaining;
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Straightforward implementation to compare against
+    fn encode_naive(bytes: &[u8]) -> String {
+        let mut output = String::new();
+        for chunk in bytes.chunks(4) {
+            let mut padded = [0; 4];
+            padded[..chunk.len()].copy_from_slice(chunk);
+            let dword = u32::from_be_bytes(padded);
+            if chunk.len() == 4 && dword == 0 {
+                output.push('z');
+                continue;
+            }
+            for i in (0..5).rev().take(chunk.len() + 1) {
+                output.push((33 + (dword / 85_u32.pow(i)) % 85) as u8 as char);
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_matches_naive() {
+        let mut state = 1_u32;
+        let bytes = (0..4099)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                // Plenty of zero chunks and extreme values
+                match i / 64 % 4 {
+                    0 => 0,
+                    1 => 0xFF,
+                    _ => (state >> 16) as u8,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for len in (0..16).chain(4090..4099) {
+            let encoded = encode(&bytes[..len]);
+            assert_eq!(encoded, encode_naive(&bytes[..len]));
+            assert_eq!(decode(encoded.as_bytes()).unwrap()[..len], bytes[..len]);
+        }
+        assert_eq!(encode(&[0xFF; 4]), "s8W-!");
+    }
+
+    #[test]
+    fn test_z_inside_group() {
+        assert_eq!(decode(b"z!!!!!").unwrap(), [0; 8]);
+        assert!(matches!(
+            decode(b"!!z!!"),
+            Err(DecodeError::InvalidCharacter { character: b'z' })
+        ));
+        assert!(matches!(
+            decode(b"!!!!!!z"),
+            Err(DecodeError::InvalidCharacter { character: b'z' })
+        ));
+    }
+}
@@ -0,0 +1,437 @@
+/*!
+Exact positions in beats and measures, convertible to and from rows
+
+ArrowVortex places notes on a grid of [`ROWS_PER_BEAT`] rows per beat. [`Beat`] and
+[`MeasurePosition`] express positions as exact [`Fraction`]s instead, and converting them to rows
+fails with [`BeatError::NotOnGrid`] if they fall between two rows. Converting rows to beats and
+measures is always exact.
+
+Measures depend on the [time signatures](crate::TempoEventKind::TimeSignature) of a chart, see
+[`Measures`].
+
+```rust
+use arrowvortex_clipboard::beat::{Beat, BeatError, Fraction, MeasurePosition, Measures};
+use arrowvortex_clipboard::{Row, TempoEvent, TempoEventKind};
+
+// A 16th note after beat 2
+let beat = Beat(Fraction::new(9, 4)?);
+assert_eq!(beat.to_row()?, Row(108));
+assert_eq!(Beat::from_row(Row(108)), beat);
+
+// 1/7 beat isn't on the grid
+let result = Beat(Fraction::new(1, 7)?).to_row();
+assert!(matches!(result, Err(BeatError::NotOnGrid { .. })));
+
+// One measure of 4/4, then 3/4
+let measures = Measures::new(&[TempoEvent {
+    row: Row(192),
+    kind: TempoEventKind::TimeSignature { numerator: 3, denominator: 4 },
+}]);
+let position = MeasurePosition { measure: 2, offset: Fraction::new(1, 3)? };
+assert_eq!(measures.measure_to_row(position)?, Row(192 + 144 + 48));
+assert_eq!(measures.row_to_measure(Row(384))?, position);
+# Ok::<(), BeatError>(())
+```
+*/
+
+use crate::{Row, TempoEvent, TempoEventKind, ROWS_PER_BEAT};
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+
+/// Error in converting between rows, beats and measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeatError {
+    /// A fraction with a denominator of zero was requested
+    ZeroDenominator,
+    /// The position falls between two rows
+    NotOnGrid {
+        /// The exact row the position corresponds to
+        row: Fraction,
+    },
+    /// The position, or a value computed from it, is too large to be represented
+    Overflow,
+    /// The offset of a [`MeasurePosition`] is not below 1, or reaches past the end of a measure
+    /// that a time signature change cut short
+    OffsetOutOfRange,
+}
+
+impl core::fmt::Display for BeatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ZeroDenominator => f.write_str("denominator is zero"),
+            Self::NotOnGrid { row } => write!(f, "position at row {} is between rows", row),
+            Self::Overflow => f.write_str("position is too large"),
+            Self::OffsetOutOfRange => {
+                f.write_str("measure offset is beyond the end of the measure")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BeatError {}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// Non-negative rational number, always in lowest terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl Fraction {
+    /// Creates the fraction `numerator / denominator`, reduced to lowest terms
+    pub fn new(numerator: u64, denominator: u64) -> Result<Self, BeatError> {
+        Self::reduced(numerator as u128, denominator as u128)
+    }
+
+    fn reduced(numerator: u128, denominator: u128) -> Result<Self, BeatError> {
+        if denominator == 0 {
+            return Err(BeatError::ZeroDenominator);
+        }
+        let divisor = gcd(numerator, denominator);
+        let convert = |n: u128| {
+            let n = n / divisor;
+            if n > u64::MAX as u128 {
+                Err(BeatError::Overflow)
+            } else {
+                Ok(n as u64)
+            }
+        };
+        Ok(Self {
+            numerator: convert(numerator)?,
+            denominator: convert(denominator)?,
+        })
+    }
+
+    /// Returns the numerator in lowest terms
+    pub fn numerator(self) -> u64 {
+        self.numerator
+    }
+
+    /// Returns the denominator in lowest terms, which is never zero
+    pub fn denominator(self) -> u64 {
+        self.denominator
+    }
+
+    /// Returns the integer part, rounded down
+    pub fn floor(self) -> u64 {
+        self.numerator / self.denominator
+    }
+
+    /// Returns the fractional part, which is below 1
+    pub fn fract(self) -> Self {
+        Self {
+            numerator: self.numerator % self.denominator,
+            denominator: self.denominator,
+        }
+    }
+
+    /// Multiplies with another fraction, failing if the result is not an integer
+    fn mul_exact(self, other: Self) -> Result<u64, BeatError> {
+        let product = Self::reduced(
+            self.numerator as u128 * other.numerator as u128,
+            self.denominator as u128 * other.denominator as u128,
+        )?;
+        match product.denominator {
+            1 => Ok(product.numerator),
+            _ => Err(BeatError::NotOnGrid { row: product }),
+        }
+    }
+}
+
+impl From<u64> for Fraction {
+    fn from(n: u64) -> Self {
+        Self {
+            numerator: n,
+            denominator: 1,
+        }
+    }
+}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as u128 * other.denominator as u128)
+            .cmp(&(other.numerator as u128 * self.denominator as u128))
+    }
+}
+
+impl core::fmt::Display for Fraction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            denominator => write!(f, "{}/{}", self.numerator, denominator),
+        }
+    }
+}
+
+/// Position in beats, counted from row 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Beat(pub Fraction);
+
+impl Beat {
+    /// Returns the beat at the given row
+    pub fn from_row(row: Row) -> Self {
+        Self(Fraction::reduced(row.0 as u128, ROWS_PER_BEAT as u128).expect("row is in range"))
+    }
+
+    /// Returns the row of this beat
+    pub fn to_row(self) -> Result<Row, BeatError> {
+        self.0.mul_exact(Fraction::from(ROWS_PER_BEAT)).map(Row)
+    }
+}
+
+/// Position in measures: the measure number, counted from 0, and how far into the measure the
+/// position is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeasurePosition {
+    /// Number of the measure, the first one being 0
+    pub measure: u64,
+    /// Fraction of the measure that has passed, below 1
+    pub offset: Fraction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MeasureSegment {
+    /// Row of the time signature change
+    row: u64,
+    /// Number of the measure starting at `row`
+    measure: u64,
+    rows_per_measure: Fraction,
+}
+
+/// Maps rows to measures, based on the time signatures of a chart
+///
+/// Every time signature change starts a new measure. If it happens in the middle of a measure,
+/// that measure is cut short. Without time signature events, all measures are in 4/4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measures {
+    segments: Vec<MeasureSegment>,
+}
+
+impl Measures {
+    /// Collects the time signatures from a list of tempo events, in any order
+    ///
+    /// Other events are ignored, as are time signatures with a numerator or denominator of zero.
+    pub fn new(tempo_events: &[TempoEvent]) -> Self {
+        let mut time_signatures = tempo_events
+            .iter()
+            .filter_map(|event| match event.kind {
+                TempoEventKind::TimeSignature {
+                    numerator,
+                    denominator,
+                } if numerator > 0 && denominator > 0 => {
+                    // A whole note is four beats
+                    let rows_per_measure =
+                        Fraction::new(numerator as u64 * 4 * ROWS_PER_BEAT, denominator as u64)
+                            .expect("denominator is not zero");
+                    Some((event.row.0, rows_per_measure))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        time_signatures.sort_by_key(|&(row, _)| row);
+
+        let mut segments = vec![MeasureSegment {
+            row: 0,
+            measure: 0,
+            rows_per_measure: Fraction::from(4 * ROWS_PER_BEAT),
+        }];
+        for (row, rows_per_measure) in time_signatures {
+            let last = segments.last_mut().unwrap();
+            if row == last.row {
+                // Later events on the same row take precedence
+                last.rows_per_measure = rows_per_measure;
+                continue;
+            }
+            // Number of measures since the last change, rounded up
+            let rows_per_measure_numerator = last.rows_per_measure.numerator as u128;
+            let measures = ((row - last.row) as u128 * last.rows_per_measure.denominator as u128
+                + rows_per_measure_numerator
+                - 1)
+                / rows_per_measure_numerator;
+            let measure = last
+                .measure
+                .saturating_add(measures.min(u64::MAX as u128) as u64);
+            segments.push(MeasureSegment {
+                row,
+                measure,
+                rows_per_measure,
+            });
+        }
+
+        Self { segments }
+    }
+
+    /// Returns the measure position of the given row
+    ///
+    /// Only fails with [`BeatError::Overflow`], for rows far beyond anything ArrowVortex supports.
+    pub fn row_to_measure(&self, row: Row) -> Result<MeasurePosition, BeatError> {
+        let row = row.0;
+        let segment = self
+            .segments
+            .iter()
+            .rfind(|segment| segment.row <= row)
+            .unwrap_or(&self.segments[0]);
+        let measures = Fraction::reduced(
+            (row - segment.row) as u128 * segment.rows_per_measure.denominator as u128,
+            segment.rows_per_measure.numerator as u128,
+        )?;
+        Ok(MeasurePosition {
+            measure: segment
+                .measure
+                .checked_add(measures.floor())
+                .ok_or(BeatError::Overflow)?,
+            offset: measures.fract(),
+        })
+    }
+
+    /// Returns the row of the given measure position
+    ///
+    /// Fails with [`BeatError::OffsetOutOfRange`] if the position is not inside its measure, which
+    /// is shorter than usual if a time signature change cuts it short.
+    pub fn measure_to_row(&self, position: MeasurePosition) -> Result<Row, BeatError> {
+        if position.offset >= Fraction::from(1) {
+            return Err(BeatError::OffsetOutOfRange);
+        }
+        let segment_index = self
+            .segments
+            .iter()
+            .rposition(|segment| segment.measure <= position.measure)
+            .unwrap_or(0);
+        let segment = &self.segments[segment_index];
+
+        let offset = position.offset;
+        let measures = Fraction::reduced(
+            ((position.measure - segment.measure) as u128)
+                .checked_mul(offset.denominator as u128)
+                .and_then(|n| n.checked_add(offset.numerator as u128))
+                .ok_or(BeatError::Overflow)?,
+            offset.denominator as u128,
+        )?;
+        if let Some(next) = self.segments.get(segment_index + 1) {
+            // Measures that fit before the next time signature change, the last one possibly cut
+            // short
+            let available = Fraction::reduced(
+                (next.row - segment.row) as u128 * segment.rows_per_measure.denominator as u128,
+                segment.rows_per_measure.numerator as u128,
+            )?;
+            if measures >= available {
+                return Err(BeatError::OffsetOutOfRange);
+            }
+        }
+        let rows = match measures.mul_exact(segment.rows_per_measure) {
+            Err(BeatError::NotOnGrid { row }) => {
+                let row = Fraction::reduced(
+                    row.numerator as u128 + segment.row as u128 * row.denominator as u128,
+                    row.denominator as u128,
+                )?;
+                return Err(BeatError::NotOnGrid { row });
+            }
+            rows => rows?,
+        };
+        rows.checked_add(segment.row)
+            .map(Row)
+            .ok_or(BeatError::Overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction(numerator: u64, denominator: u64) -> Fraction {
+        Fraction::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn test_beat() {
+        assert_eq!(fraction(6, 8), fraction(3, 4));
+        assert!(fraction(2, 3) > fraction(5, 8));
+        assert_eq!(Fraction::new(1, 0), Err(BeatError::ZeroDenominator));
+
+        for row in &[0, 1, 12, 47, 48, 1000, u64::MAX] {
+            assert_eq!(Beat::from_row(Row(*row)).to_row(), Ok(Row(*row)));
+        }
+        assert_eq!(
+            Beat(fraction(1, 7)).to_row(),
+            Err(BeatError::NotOnGrid {
+                row: fraction(48, 7)
+            })
+        );
+        assert_eq!(Beat(u64::MAX.into()).to_row(), Err(BeatError::Overflow));
+    }
+
+    #[test]
+    fn test_measures() {
+        let time_signature = |row, numerator, denominator| TempoEvent {
+            row: Row(row),
+            kind: TempoEventKind::TimeSignature {
+                numerator,
+                denominator,
+            },
+        };
+        // 4/4, then 7/8 from row 192, then 2/4 from the middle of a 7/8 measure
+        let measures = Measures::new(&[
+            time_signature(444, 2, 4),
+            time_signature(192, 7, 8),
+            time_signature(500, 0, 4),
+        ]);
+
+        let position = |measure, numerator, denominator| MeasurePosition {
+            measure,
+            offset: fraction(numerator, denominator),
+        };
+        let cases = [
+            (0, position(0, 0, 1)),
+            (96, position(0, 1, 2)),
+            (192, position(1, 0, 1)),
+            (192 + 168 + 42, position(2, 1, 4)),
+            (444, position(3, 0, 1)),
+            (444 + 48, position(3, 1, 2)),
+        ];
+        for (row, position) in cases.iter().copied() {
+            assert_eq!(measures.row_to_measure(Row(row)), Ok(position));
+            assert_eq!(measures.measure_to_row(position), Ok(Row(row)));
+        }
+        // The 7/8 measure that was cut short
+        assert_eq!(
+            measures.row_to_measure(Row(192 + 168)),
+            Ok(position(2, 0, 1))
+        );
+
+        assert_eq!(
+            measures.measure_to_row(position(0, 1, 5)),
+            Err(BeatError::NotOnGrid {
+                row: fraction(192, 5)
+            })
+        );
+        assert_eq!(
+            measures.measure_to_row(position(0, 1, 1)),
+            Err(BeatError::OffsetOutOfRange)
+        );
+        // Only the first half of the cut short 7/8 measure exists
+        assert_eq!(
+            measures.measure_to_row(position(2, 1, 2)),
+            Err(BeatError::OffsetOutOfRange)
+        );
+        assert_eq!(
+            measures.measure_to_row(position(2, 3, 7)),
+            Ok(Row(192 + 168 + 72))
+        );
+    }
+}
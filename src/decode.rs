@@ -1,4 +1,6 @@
-use crate::{Note, NoteKind, TempoEvent, TempoEventKind};
+use crate::position::sealed::Sealed;
+use crate::{Note, NoteKind, NotePosition, Row, TempoEvent, TempoEventKind};
+use alloc::vec::Vec;
 
 /// Error in [`decode`] call
 #[derive(Debug)]
@@ -19,6 +21,46 @@ pub enum DecodeError {
         /// The unknown tempo event type integer that was encountered
         tempo_event_type: u8,
     },
+    /// Input contained a character that is not part of the clipboard encoding
+    InvalidCharacter {
+        /// The invalid character
+        character: u8,
+    },
+    /// Input contained a variable-length integer longer than [`DecodeLimits::max_varint_bytes`]
+    VarintTooLong {
+        /// The limit that was exceeded
+        limit: usize,
+    },
+    /// Input contained a variable-length integer that doesn't fit into 64 bits
+    VarintOverflow,
+    /// Input contained more notes than [`DecodeLimits::max_notes`]
+    TooManyNotes {
+        /// The limit that was exceeded
+        limit: usize,
+    },
+    /// Input contained more tempo events than [`DecodeLimits::max_tempo_events`]
+    TooManyTempoEvents {
+        /// The limit that was exceeded
+        limit: usize,
+    },
+    /// Input contained a label longer than [`DecodeLimits::max_label_len`]
+    LabelTooLong {
+        /// The limit that was exceeded
+        limit: usize,
+    },
+    /// Input contains a different kind of data than was requested, for example tempo events
+    /// instead of notes, or time-based instead of row-based notes
+    UnexpectedDataType,
+    /// Input contained a note position that [`NotePosition::from_raw`] rejected
+    InvalidPosition,
+    /// Input contained a note in a column that the requested game mode doesn't have, see
+    /// [`Selection::decode`](crate::mode::Selection::decode)
+    ColumnOutOfRange {
+        /// The column that is out of range
+        column: u8,
+        /// Number of columns of the game mode
+        num_columns: u8,
+    },
 }
 
 impl core::fmt::Display for DecodeError {
@@ -31,50 +73,96 @@ impl core::fmt::Display for DecodeError {
             Self::UnknownTempoEventType { tempo_event_type } => {
                 write!(f, "unknown tempo event type {}", tempo_event_type)
             }
+            Self::InvalidCharacter { character } => {
+                write!(f, "invalid character {:?}", *character as char)
+            }
+            Self::VarintTooLong { limit } => write!(f, "integer longer than {} bytes", limit),
+            Self::VarintOverflow => f.write_str("integer does not fit into 64 bits"),
+            Self::TooManyNotes { limit } => write!(f, "more than {} notes", limit),
+            Self::TooManyTempoEvents { limit } => write!(f, "more than {} tempo events", limit),
+            Self::LabelTooLong { limit } => write!(f, "label longer than {} bytes", limit),
+            Self::UnexpectedDataType => f.write_str("clipboard contains a different kind of data"),
+            Self::InvalidPosition => f.write_str("note position cannot be represented"),
+            Self::ColumnOutOfRange {
+                column,
+                num_columns,
+            } => write!(
+                f,
+                "column {} is out of range for {} columns",
+                column, num_columns
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for DecodeError {}
 
-/// Convert `data` from AV clipboard format into bytes
-fn decode_dwords_from_base85(data: &[u8]) -> impl Iterator<Item = u8> + '_ {
-    let mut data = data.iter().copied();
-
-    // ArrowVortex groups bytes into 32bit ints and encodes them in base85 starting from ASCII 33.
-    // Every 32bit int is represented by 5 base85 digits
-    core::iter::from_fn(move || {
-        let first_char = data.next()?;
-        // 'z' is a shorthand for an entire zero chunk
-        if first_char == b'z' {
-            return Some([0, 0, 0, 0]);
-        }
+/// Limits on the size of decoded data, to protect against corrupt or malicious input. Used by
+/// [`decode_with_limits`]
+///
+/// Data that exceeds a limit fails to decode with an error instead of allocating large amounts of
+/// memory. The defaults are far above anything ArrowVortex produces in practice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum number of notes
+    pub max_notes: usize,
+    /// Maximum number of tempo events, across all types
+    pub max_tempo_events: usize,
+    /// Maximum length of a label message in bytes
+    pub max_label_len: usize,
+    /// Maximum number of bytes in a variable-length integer. Ten bytes are enough for any 64-bit
+    /// value, so larger limits are lowered to ten
+    pub max_varint_bytes: usize,
+}
 
-        let dword = 85_u32.pow(4) * (first_char - 33) as u32
-            + 85_u32.pow(3) * data.next().map_or(85, |b| b - 33) as u32
-            + 85_u32.pow(2) * data.next().map_or(85, |b| b - 33) as u32
-            + 85_u32.pow(1) * data.next().map_or(85, |b| b - 33) as u32
-            + 85_u32.pow(0) * data.next().map_or(85, |b| b - 33) as u32;
-        Some(dword.to_be_bytes())
-    })
-    .flat_map(core::array::IntoIter::new)
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_notes: 1_000_000,
+            max_tempo_events: 100_000,
+            max_label_len: 65536,
+            max_varint_bytes: 10,
+        }
+    }
 }
 
+/// Number of bytes needed for any 64-bit variable-length integer
+const MAX_VARINT_BYTES: usize = 10;
+
+/// Upper limit for the buffer that is preallocated for label messages
+const MAX_PREALLOCATED_LABEL_LEN: u64 = 1024;
+
+/// Upper limit for the buffer that is preallocated for notes
+const MAX_PREALLOCATED_NOTES: u64 = 4096;
+
 #[inline(never)]
 // TODO: return i32 instead?
-fn decode_varint(data: &mut dyn Iterator<Item = u8>) -> Result<u64, DecodeError> {
+fn decode_varint(
+    data: &mut dyn Iterator<Item = u8>,
+    limits: &DecodeLimits,
+) -> Result<u64, DecodeError> {
+    let max_bytes = limits.max_varint_bytes.min(MAX_VARINT_BYTES);
     let mut result = 0;
-    for i in 0.. {
+    let mut overflowed = false;
+    for i in 0..max_bytes {
         let byte = data.next().ok_or(DecodeError::UnexpectedEof)?;
         let is_last_byte = byte & 0x80 == 0;
-        let varint_digit = byte & 0x7F;
+        let varint_digit = (byte & 0x7F) as u64;
 
-        result |= (varint_digit as u64) << (7 * i);
+        // The shift is at most 63, but the top bits of the tenth byte don't fit into the result
+        let shift = 7 * i as u32;
+        overflowed |= (varint_digit << shift) >> shift != varint_digit;
+        result |= varint_digit << shift;
         if is_last_byte {
-            break;
+            return if overflowed {
+                Err(DecodeError::VarintOverflow)
+            } else {
+                Ok(result)
+            };
         }
     }
-    Ok(result)
+    Err(DecodeError::VarintTooLong { limit: max_bytes })
 }
 
 fn decode_f64(data: &mut dyn Iterator<Item = u8>) -> Result<f64, DecodeError> {
@@ -90,23 +178,41 @@ fn decode_f64(data: &mut dyn Iterator<Item = u8>) -> Result<f64, DecodeError> {
     ]))
 }
 
-fn decode_notes<'a, P: 'static>(
+fn decode_position<P: NotePosition>(
+    data: &mut dyn Iterator<Item = u8>,
+    limits: &DecodeLimits,
+) -> Result<P, DecodeError> {
+    let raw = if P::Raw::TIME_BASED {
+        P::Raw::from_bits(decode_f64(data)?.to_bits())
+    } else {
+        P::Raw::from_bits(decode_varint(data, limits)?)
+    };
+    P::from_raw(raw).ok_or(DecodeError::InvalidPosition)
+}
+
+fn read_notes<'a, P: NotePosition>(
     mut data: impl Iterator<Item = u8> + 'a,
-    position_decode: fn(&mut dyn Iterator<Item = u8>) -> Result<P, DecodeError>,
+    limits: &DecodeLimits,
 ) -> Result<Vec<Note<P>>, DecodeError> {
-    let size = decode_varint(&mut data)?;
+    let size = decode_varint(&mut data, limits)?;
+    if size > limits.max_notes as u64 {
+        return Err(DecodeError::TooManyNotes {
+            limit: limits.max_notes,
+        });
+    }
 
-    let notes = (0..size).map(move |_| {
+    let mut notes = Vec::with_capacity(size.min(MAX_PREALLOCATED_NOTES) as usize);
+    for _ in 0..size {
         let first_byte = data.next().ok_or(DecodeError::UnexpectedEof)?;
         let is_tap = first_byte & 0x80 == 0;
         let column = first_byte & 0x7F;
 
-        let pos = position_decode(&mut data)?;
+        let pos = decode_position(&mut data, limits)?;
 
         let note_kind = if is_tap {
             NoteKind::Tap
         } else {
-            let end_pos = position_decode(&mut data)?;
+            let end_pos = decode_position(&mut data, limits)?;
             match data.next().ok_or(DecodeError::UnexpectedEof)? {
                 0 => NoteKind::Hold { end_pos },
                 1 => NoteKind::Mine,
@@ -117,13 +223,13 @@ fn decode_notes<'a, P: 'static>(
             }
         };
 
-        Ok(Note {
+        notes.push(Note {
             pos,
             column,
             kind: note_kind,
-        })
-    });
-    notes.collect()
+        });
+    }
+    Ok(notes)
 }
 
 fn decode_u32(data: &mut dyn Iterator<Item = u8>) -> Result<u32, DecodeError> {
@@ -138,6 +244,7 @@ fn decode_u32(data: &mut dyn Iterator<Item = u8>) -> Result<u32, DecodeError> {
 fn decode_single_tempo_event(
     data: &mut dyn Iterator<Item = u8>,
     kind: u8,
+    limits: &DecodeLimits,
 ) -> Result<TempoEvent, DecodeError> {
     let pos = decode_u32(data)?;
     let kind = match kind {
@@ -176,8 +283,15 @@ fn decode_single_tempo_event(
             num_fake_rows: decode_u32(data)?,
         },
         10 => {
-            let message_len = decode_varint(data)?;
-            let mut message = Vec::with_capacity(message_len as usize);
+            let message_len = decode_varint(data, limits)?;
+            if message_len > limits.max_label_len as u64 {
+                return Err(DecodeError::LabelTooLong {
+                    limit: limits.max_label_len,
+                });
+            }
+            // The length may be corrupt, so only preallocate a sane amount
+            let mut message =
+                Vec::with_capacity(message_len.min(MAX_PREALLOCATED_LABEL_LEN) as usize);
             for _ in 0..message_len {
                 message.push(data.next().ok_or(DecodeError::UnexpectedEof)?);
             }
@@ -189,46 +303,41 @@ fn decode_single_tempo_event(
             })
         }
     };
-    Ok(TempoEvent { row: pos, kind })
+    Ok(TempoEvent {
+        row: Row::from(pos),
+        kind,
+    })
 }
 
 fn decode_tempo<'a>(
     mut data: impl Iterator<Item = u8> + 'a,
+    limits: &DecodeLimits,
 ) -> Result<Vec<TempoEvent>, DecodeError> {
-    let mut count = decode_varint(&mut data)?;
-    let mut kind = None;
-
-    core::iter::from_fn(move || {
+    let mut tempo_events = Vec::new();
+    // Events are grouped by type. Each group starts with its size and type, and a group of size
+    // zero ends the list
+    loop {
+        let count = decode_varint(&mut data, limits)?;
         if count == 0 {
-            return None;
-        };
-
-        if kind.is_none() {
-            kind = Some(match data.next() {
-                Some(x) => x,
-                None => return Some(Err(DecodeError::UnexpectedEof)),
+            return Ok(tempo_events);
+        }
+        if count > (limits.max_tempo_events - tempo_events.len()) as u64 {
+            return Err(DecodeError::TooManyTempoEvents {
+                limit: limits.max_tempo_events,
             });
         }
-        let event = decode_single_tempo_event(&mut data, kind.unwrap());
 
-        count -= 1;
-        if count == 0 {
-            count = match decode_varint(&mut data) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e)),
-            };
-            kind = None;
+        let kind = data.next().ok_or(DecodeError::UnexpectedEof)?;
+        for _ in 0..count {
+            tempo_events.push(decode_single_tempo_event(&mut data, kind, limits)?);
         }
-
-        Some(event)
-    })
-    .collect()
+    }
 }
 
 /// Possible contents of ArrowVortex clipboard data. Returned by [`decode()`].
 pub enum DecodeResult {
     /// Row based notes copy (most common)
-    RowBasedNotes(Vec<Note<u64>>),
+    RowBasedNotes(Vec<Note<Row>>),
     /// Time based notes copy (if you enabled Time Based Copy in the menu)
     TimeBasedNotes(Vec<Note<f64>>),
     /// Tempo events copy
@@ -238,7 +347,7 @@ pub enum DecodeResult {
 /// Decodes a byte buffer into an iterator of [`Note`]
 ///
 /// ```rust
-/// use arrowvortex_clipboard::{Note, NoteKind};
+/// use arrowvortex_clipboard::{Note, NoteKind, Row};
 ///
 /// let data = br#"ArrowVortex:notes:!!E9%!=T#H"!d"#;
 ///
@@ -248,53 +357,162 @@ pub enum DecodeResult {
 /// };
 ///
 /// assert_eq!(&notes, &[
-///     Note { pos: 0, column: 0, kind: NoteKind::Tap },
-///     Note { pos: 12, column: 1, kind: NoteKind::Tap },
-///     Note { pos: 24, column: 2, kind: NoteKind::Tap },
-///     Note { pos: 36, column: 3, kind: NoteKind::Tap },
+///     Note { pos: Row(0), column: 0, kind: NoteKind::Tap },
+///     Note { pos: Row(12), column: 1, kind: NoteKind::Tap },
+///     Note { pos: Row(24), column: 2, kind: NoteKind::Tap },
+///     Note { pos: Row(36), column: 3, kind: NoteKind::Tap },
 /// ]);
 ///
 /// # Ok::<(), arrowvortex_clipboard::DecodeError>(())
 /// ```
 pub fn decode(data: &[u8]) -> Result<DecodeResult, DecodeError> {
-    let (data, is_tempo) = if let Some(data) = data.strip_prefix(b"ArrowVortex:notes:") {
-        (data, false)
-    } else if let Some(data) = data.strip_prefix(b"ArrowVortex:tempo:") {
-        (data, true)
-    } else {
-        return Err(DecodeError::MissingSignature);
-    };
+    decode_with_limits(data, &DecodeLimits::default())
+}
 
-    let mut data = decode_dwords_from_base85(data);
+/// Like [`decode`], but with custom limits on the size of the decoded data
+///
+/// Use this for untrusted input. Corrupt or malicious data produces an error instead of a panic
+/// or a huge allocation.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{DecodeError, DecodeLimits};
+///
+/// let data = br#"ArrowVortex:notes:!!E9%!=T#H"!d"#;
+/// let limits = DecodeLimits { max_notes: 3, ..Default::default() };
+///
+/// let result = arrowvortex_clipboard::decode_with_limits(data, &limits);
+/// assert!(matches!(result, Err(DecodeError::TooManyNotes { limit: 3 })));
+/// ```
+pub fn decode_with_limits(data: &[u8], limits: &DecodeLimits) -> Result<DecodeResult, DecodeError> {
+    let (bytes, is_tempo) = decode_base85(data)?;
+    let mut data = bytes.iter().copied();
 
     Ok(if is_tempo {
-        DecodeResult::TempoEvents(decode_tempo(data)?)
+        DecodeResult::TempoEvents(decode_tempo(data, limits)?)
     } else {
         let is_time_based = data.next().ok_or(DecodeError::UnexpectedEof)? != 0;
 
         if is_time_based {
-            DecodeResult::TimeBasedNotes(decode_notes(data, decode_f64)?)
+            DecodeResult::TimeBasedNotes(read_notes(data, limits)?)
         } else {
-            DecodeResult::RowBasedNotes(decode_notes(data, decode_varint)?)
+            DecodeResult::RowBasedNotes(read_notes(data, limits)?)
         }
     })
 }
 
+/// Strips the signature and decodes the base85 payload. Returns whether it contains tempo events
+fn decode_base85(data: &[u8]) -> Result<(Vec<u8>, bool), DecodeError> {
+    let (data, is_tempo) = if let Some(data) = data.strip_prefix(b"ArrowVortex:notes:") {
+        (data, false)
+    } else if let Some(data) = data.strip_prefix(b"ArrowVortex:tempo:") {
+        (data, true)
+    } else {
+        return Err(DecodeError::MissingSignature);
+    };
+    Ok((crate::base85::decode(data)?, is_tempo))
+}
+
+/// Decodes notes into a custom position type
+///
+/// Fails with [`DecodeError::UnexpectedDataType`] if the input doesn't contain notes of the
+/// position's [raw representation](NotePosition::Raw). See [`NotePosition`] for an example.
+pub fn decode_notes<P: NotePosition>(data: &[u8]) -> Result<Vec<Note<P>>, DecodeError> {
+    decode_notes_with_limits(data, &DecodeLimits::default())
+}
+
+/// Like [`decode_notes`], but with custom limits on the size of the decoded data
+pub fn decode_notes_with_limits<P: NotePosition>(
+    data: &[u8],
+    limits: &DecodeLimits,
+) -> Result<Vec<Note<P>>, DecodeError> {
+    let (bytes, is_tempo) = decode_base85(data)?;
+    let mut data = bytes.iter().copied();
+
+    if is_tempo {
+        return Err(DecodeError::UnexpectedDataType);
+    }
+    let is_time_based = data.next().ok_or(DecodeError::UnexpectedEof)? != 0;
+    if is_time_based != P::Raw::TIME_BASED {
+        return Err(DecodeError::UnexpectedDataType);
+    }
+    read_notes(data, limits)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_decode_dwords_from_base85() {
-        let bytes = decode_dwords_from_base85(b"alphagamma").collect::<Vec<u8>>();
-        assert_eq!(bytes, [0xC9, 0xE8, 0xC9, 0x19, 0xDC, 0x2C, 0x7E, 0x0E]);
+    fn test_decode_varint() {
+        let bytes = [
+            0xBD, 0xC7, 0x03, 0xF0, 0x0D, 0xBA, 0xAD, 0xF0, 0x0D, 0xBA, 0xAD,
+        ];
+        let limits = DecodeLimits::default();
+        assert_eq!(
+            decode_varint(&mut bytes.iter().copied(), &limits).unwrap(),
+            58301
+        );
+
+        // Endless continuation bits
+        let result = decode_varint(&mut core::iter::repeat(0xFF), &limits);
+        assert!(matches!(
+            result,
+            Err(DecodeError::VarintTooLong { limit: 10 })
+        ));
+        let generous_limits = DecodeLimits {
+            max_varint_bytes: usize::MAX,
+            ..Default::default()
+        };
+        let result = decode_varint(&mut core::iter::repeat(0xFF), &generous_limits);
+        assert!(matches!(
+            result,
+            Err(DecodeError::VarintTooLong { limit: 10 })
+        ));
+
+        let mut largest = [0xFF; 10];
+        largest[9] = 0x01;
+        assert_eq!(
+            decode_varint(&mut largest.iter().copied(), &limits).unwrap(),
+            u64::MAX
+        );
+        let mut too_large = largest;
+        too_large[9] = 0x02;
+        let result = decode_varint(&mut too_large.iter().copied(), &limits);
+        assert!(matches!(result, Err(DecodeError::VarintOverflow)));
     }
 
     #[test]
-    fn test_decode_varint() {
+    fn test_corrupt_label_len() {
+        // Row 0, then a message length of 2^62 with only two bytes of message following
         let bytes = [
-            0xBD, 0xC7, 0x03, 0xF0, 0x0D, 0xBA, 0xAD, 0xF0, 0x0D, 0xBA, 0xAD,
+            0, 0, 0, 0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x40, b'h', b'i',
         ];
-        assert_eq!(decode_varint(&mut bytes.iter().copied()).unwrap(), 58301);
+        let limits = DecodeLimits {
+            max_label_len: usize::MAX,
+            ..Default::default()
+        };
+        let result = decode_single_tempo_event(&mut bytes.iter().copied(), 10, &limits);
+        assert!(matches!(result, Err(DecodeError::UnexpectedEof)));
+
+        let result = decode_single_tempo_event(&mut bytes.iter().copied(), 10, &Default::default());
+        assert!(matches!(
+            result,
+            Err(DecodeError::LabelTooLong { limit: 65536 })
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_input() {
+        for data in &[
+            &b"ArrowVortex:notes:uuuuuuuuuuuuuuu"[..],
+            b"ArrowVortex:tempo:uuuuuuuuuuuuuuu",
+            b"ArrowVortex:notes:!s8W-!s8W-!s8W-!s8W-",
+        ] {
+            assert!(decode(data).is_err());
+        }
+        assert!(matches!(
+            decode(b"ArrowVortex:notes:!!E9%!=T#H\"!d\n"),
+            Err(DecodeError::InvalidCharacter { character: b'\n' })
+        ));
     }
 }
@@ -0,0 +1,285 @@
+/*!
+Rough difficulty estimation for note selections
+
+Difficulty models implement [`DifficultyCalculator`]. The built-in [`DanceSingle`] model rates
+selections in units of notes per second that a player needs to sustain, so a rating of 10 is about
+as hard as a few seconds of 16th stream at 150 BPM.
+
+```rust
+use arrowvortex_clipboard::{difficulty::{DanceSingle, DifficultyCalculator}, text};
+use arrowvortex_clipboard::{Row, TempoEvent, TempoEventKind};
+
+let notes = text::parse_notes(&(0..64)
+    .map(|i| format!("{} {}\n", i * 12, ["1000", "0100", "0001", "0010"][i % 4]))
+    .collect::<String>())?;
+let tempo_events = [TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 150.0 } }];
+
+let difficulty = DanceSingle::default().calculate(&notes, &tempo_events);
+assert!(difficulty.skillsets.stream > difficulty.skillsets.jacks);
+assert!((9.0..12.0).contains(&difficulty.overall));
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::patterns::{self, Pattern};
+use crate::timing::Timing;
+use crate::{Note, NoteKind, Row, TempoEvent};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Ratings of the individual skills a selection demands
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Skillsets {
+    /// Fast single-note streams
+    pub stream: f64,
+    /// Streams with jumps or hands
+    pub jumpstream: f64,
+    /// Repeated hits on the same column, including chordjacks
+    pub jacks: f64,
+    /// Patterns that require awkward movement, like crossovers, candles and footswitches
+    pub technical: f64,
+}
+
+/// Result of a [`DifficultyCalculator`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Difficulty {
+    /// Overall rating
+    pub overall: f64,
+    /// Per-skillset ratings, on the same scale as the overall rating
+    pub skillsets: Skillsets,
+}
+
+/// A model that rates the difficulty of a note selection
+pub trait DifficultyCalculator {
+    /// Rates `notes`, using `tempo_events` to determine when each note is to be hit
+    fn calculate(&self, notes: &[Note<Row>], tempo_events: &[TempoEvent]) -> Difficulty;
+}
+
+/// Difficulty model for dance-single (four panels, played with the feet)
+///
+/// The model looks at note density over real time, rates streams, jumpstreams, jacks and
+/// technical patterns (see [`patterns`]) separately, and boosts every rating for selections that
+/// stay dense for a long time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanceSingle {
+    /// Length of the sliding window over which note density is measured, in seconds
+    pub window_seconds: f64,
+    /// Pattern sections shorter than this many seconds count proportionally less
+    pub min_section_seconds: f64,
+    /// How strongly long, dense sections increase the rating
+    pub stamina_weight: f64,
+}
+
+impl Default for DanceSingle {
+    fn default() -> Self {
+        Self {
+            window_seconds: 2.0,
+            min_section_seconds: 2.0,
+            stamina_weight: 0.15,
+        }
+    }
+}
+
+struct TimedRow {
+    time: f64,
+    columns: Vec<u8>,
+}
+
+impl DanceSingle {
+    /// Notes per second in the sliding window starting at each row
+    fn densities(&self, rows: &[TimedRow]) -> Vec<f64> {
+        let mut end = 0;
+        let mut num_notes = 0;
+        (0..rows.len())
+            .map(|start| {
+                // The window always includes its first row, even if times run backwards
+                while end < rows.len()
+                    && (end <= start || rows[end].time < rows[start].time + self.window_seconds)
+                {
+                    num_notes += rows[end].columns.len();
+                    end += 1;
+                }
+                let density = num_notes as f64 / self.window_seconds;
+                num_notes -= rows[start].columns.len();
+                density
+            })
+            .collect()
+    }
+
+    /// Notes per second within the section, discounted if the section is short
+    fn section_rating(&self, rows: &[TimedRow]) -> f64 {
+        let (first, last) = match (rows.first(), rows.last()) {
+            (Some(first), Some(last)) if rows.len() >= 2 => (first, last),
+            _ => return 0.0,
+        };
+        // Count the time to the note after the last one, approximated by the average spacing
+        let duration = (last.time - first.time) * rows.len() as f64 / (rows.len() - 1) as f64;
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let num_notes = rows.iter().map(|row| row.columns.len()).sum::<usize>();
+        let nps = num_notes as f64 / duration;
+        nps * (duration / self.min_section_seconds).min(1.0)
+    }
+
+    /// Hits per second of the fastest jacks, scaled to be comparable with stream density
+    fn jack_rating(&self, rows: &[TimedRow]) -> f64 {
+        let mut last_hit = alloc::collections::BTreeMap::new();
+        let mut speeds = Vec::new();
+        for row in rows {
+            for &column in &row.columns {
+                if let Some(prev_time) = last_hit.insert(column, row.time) {
+                    if row.time > prev_time {
+                        speeds.push(1.0 / (row.time - prev_time));
+                    }
+                }
+            }
+        }
+        speeds.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        // Averaging over several hits discounts single isolated minijacks
+        const NUM_FASTEST: usize = 8;
+        let fastest = &speeds[..speeds.len().min(NUM_FASTEST)];
+        let average = fastest.iter().sum::<f64>() / NUM_FASTEST as f64;
+        // Jacking with one foot is roughly as hard as streaming with two at twice the speed
+        average * 2.0
+    }
+}
+
+impl DifficultyCalculator for DanceSingle {
+    fn calculate(&self, notes: &[Note<Row>], tempo_events: &[TempoEvent]) -> Difficulty {
+        let timing = Timing::new(tempo_events);
+        let mut rows = alloc::collections::BTreeMap::<u64, Vec<u8>>::new();
+        for note in notes {
+            match note.kind {
+                NoteKind::Mine | NoteKind::Fake => {}
+                _ => rows.entry(note.pos.0).or_default().push(note.column),
+            }
+        }
+        let row_positions = rows.keys().copied().collect::<Vec<_>>();
+        let rows = rows
+            .into_iter()
+            .map(|(pos, columns)| TimedRow {
+                time: timing.row_to_time(Row(pos)),
+                columns,
+            })
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Difficulty::default();
+        }
+
+        let densities = self.densities(&rows);
+        let mut sorted_densities = densities.clone();
+        sorted_densities.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let num_peak = (sorted_densities.len() / 10).max(1);
+        let peak_density = sorted_densities[..num_peak].iter().sum::<f64>() / num_peak as f64;
+
+        // Seconds spent at least close to peak density. Negative stops can make time run
+        // backwards, which doesn't count
+        let sustained_seconds = densities
+            .iter()
+            .zip(rows.windows(2))
+            .filter(|&(&density, _)| density >= 0.75 * peak_density)
+            .map(|(_, pair)| (pair[1].time - pair[0].time).max(0.0))
+            .sum::<f64>();
+        let stamina = 1.0 + self.stamina_weight * (1.0 + sustained_seconds / 30.0).ln();
+
+        let row_index = |row: u64| row_positions.binary_search(&row).unwrap_or(0);
+        let segments = patterns::detect(notes, &patterns::PatternOptions::default());
+        let mut skillsets = Skillsets::default();
+        let mut num_technical = 0;
+        for segment in &segments {
+            let section = &rows[row_index(segment.start_row.0)..=row_index(segment.end_row.0)];
+            match segment.pattern {
+                Pattern::Stream => {
+                    skillsets.stream = skillsets.stream.max(self.section_rating(section))
+                }
+                Pattern::Jumpstream | Pattern::Handstream => {
+                    skillsets.jumpstream = skillsets.jumpstream.max(self.section_rating(section))
+                }
+                Pattern::Chordjacks => {
+                    skillsets.jacks = skillsets.jacks.max(self.jack_rating(section))
+                }
+                Pattern::Candle | Pattern::Crossover | Pattern::Footswitch => num_technical += 1,
+                _ => {}
+            }
+        }
+        skillsets.jacks = skillsets.jacks.max(self.jack_rating(&rows));
+        let technical_share = (num_technical as f64 * 4.0 / rows.len() as f64).min(1.0);
+        skillsets.technical = peak_density * (0.5 + 0.5 * technical_share);
+
+        for rating in &mut [
+            &mut skillsets.stream,
+            &mut skillsets.jumpstream,
+            &mut skillsets.jacks,
+            &mut skillsets.technical,
+        ] {
+            **rating *= stamina;
+        }
+        let overall = skillsets
+            .stream
+            .max(skillsets.jumpstream)
+            .max(skillsets.jacks)
+            .max(skillsets.technical)
+            .max(peak_density * stamina * 0.8);
+
+        Difficulty { overall, skillsets }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempoEventKind;
+
+    fn rate(text: &str, bpm: f64) -> Difficulty {
+        let notes = crate::text::parse_notes(text).unwrap();
+        let tempo_events = [TempoEvent {
+            row: Row(0),
+            kind: TempoEventKind::Bpm { bpm },
+        }];
+        DanceSingle::default().calculate(&notes, &tempo_events)
+    }
+
+    fn repeat(rows: &[&str], count: usize, spacing: u64) -> String {
+        (0..count)
+            .map(|i| format!("{} {}\n", i as u64 * spacing, rows[i % rows.len()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_skillsets() {
+        let stream = rate(&repeat(&["1000", "0010", "0100", "0001"], 64, 12), 150.0);
+        let jumpstream = rate(&repeat(&["1001", "0010", "0100", "0110"], 64, 12), 150.0);
+        let jacks = rate(&repeat(&["1000"], 32, 12), 150.0);
+
+        assert!(stream.skillsets.stream > stream.skillsets.jumpstream);
+        assert!(jumpstream.skillsets.jumpstream > jumpstream.skillsets.stream);
+        assert!(jumpstream.overall > stream.overall);
+        assert!(jacks.skillsets.jacks > jacks.skillsets.stream);
+
+        // Faster is harder
+        let faster_stream = rate(&repeat(&["1000", "0010", "0100", "0001"], 64, 12), 200.0);
+        assert!(faster_stream.overall > stream.overall);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(rate("", 120.0), Difficulty::default());
+    }
+
+    #[test]
+    fn test_odd_timing() {
+        let notes = crate::text::parse_notes(&repeat(&["1000", "0100"], 16, 12)).unwrap();
+        for &time in &[f64::NAN, f64::INFINITY, -100.0] {
+            let tempo_events = [TempoEvent {
+                row: Row(48),
+                kind: TempoEventKind::Stop { time },
+            }];
+            let difficulty = DanceSingle::default().calculate(&notes, &tempo_events);
+            assert!(!difficulty.overall.is_nan());
+        }
+    }
+}
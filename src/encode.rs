@@ -1,4 +1,6 @@
-use crate::{Note, NoteKind, TempoEvent, TempoEventKind};
+use crate::position::sealed::Sealed;
+use crate::{Note, NoteKind, NotePosition, Row, TempoEvent, TempoEventKind};
+use alloc::string::String;
 
 /// Error that may occur during any of the encoding functions
 #[derive(Debug)]
@@ -7,6 +9,24 @@ pub enum EncodeError {
     Write(core::fmt::Error),
     /// Input data was not sorted
     NotSorted,
+    /// The output buffer passed to [`encode_to_slice`] is too small
+    BufferTooSmall {
+        /// Number of bytes the encoded data needs
+        required: usize,
+    },
+    /// A note or tempo event is beyond [`Row::MAX`], the last row that ArrowVortex supports
+    RowOutOfRange {
+        /// The row that is out of range
+        row: Row,
+    },
+    /// A note is in a column that cannot be encoded: 128 and above for the encoding functions, or
+    /// beyond the game mode for [`Selection::encode`](crate::mode::Selection::encode)
+    ColumnOutOfRange {
+        /// The column that is out of range
+        column: u8,
+        /// Number of columns that are allowed
+        num_columns: u8,
+    },
 }
 
 impl core::fmt::Display for EncodeError {
@@ -14,19 +34,40 @@ impl core::fmt::Display for EncodeError {
         match self {
             EncodeError::Write(w) => w.fmt(f),
             EncodeError::NotSorted => f.write_str("given notes are not sorted"),
+            EncodeError::BufferTooSmall { required } => {
+                write!(f, "output buffer is too small, {} bytes required", required)
+            }
+            EncodeError::RowOutOfRange { row } => write!(f, "row {} is out of range", row.0),
+            EncodeError::ColumnOutOfRange {
+                column,
+                num_columns,
+            } => write!(
+                f,
+                "column {} is out of range for {} columns",
+                column, num_columns
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for EncodeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             EncodeError::Write(w) => Some(w),
-            EncodeError::NotSorted => None,
+            EncodeError::NotSorted
+            | EncodeError::BufferTooSmall { .. }
+            | EncodeError::RowOutOfRange { .. }
+            | EncodeError::ColumnOutOfRange { .. } => None,
         }
     }
 }
 
+/// Destination of the bytes that make up the encoded data, before they are turned into base85
+trait ByteSink {
+    fn write(&mut self, byte: u8) -> Result<(), EncodeError>;
+}
+
 struct Base85Encoder<'a> {
     buffer: [u8; 4],
     buffer_i: usize,
@@ -43,17 +84,6 @@ impl<'a> Base85Encoder<'a> {
         }
     }
 
-    // #[inline(never)] slows this down
-    pub fn write(&mut self, byte: u8) -> Result<(), EncodeError> {
-        // Fill next buffer slot. If buffer isn't full yet, we're done
-        self.buffer[self.buffer_i] = byte;
-        self.buffer_i += 1;
-        if self.buffer_i == 4 {
-            self.flush_buffer()?;
-        }
-        Ok(())
-    }
-
     // #[inline(never)] slows this down
     pub fn flush_buffer(&mut self) -> Result<(), EncodeError> {
         if self.buffer_i == 0 {
@@ -63,14 +93,7 @@ impl<'a> Base85Encoder<'a> {
         // Fill uninitialized bytes with zero
         self.buffer[self.buffer_i..].fill(0);
 
-        let dword = u32::from_be_bytes(self.buffer);
-        let buffer = [
-            33 + ((dword / 85_u32.pow(4)) % 85) as u8,
-            33 + ((dword / 85_u32.pow(3)) % 85) as u8,
-            33 + ((dword / 85_u32.pow(2)) % 85) as u8,
-            33 + ((dword / 85_u32.pow(1)) % 85) as u8,
-            33 + ((dword / 85_u32.pow(0)) % 85) as u8,
-        ];
+        let buffer = crate::base85::encode_dword(u32::from_be_bytes(self.buffer));
         let buffer = &buffer[..(1 + self.buffer_i)];
         self.buffer_i = 0;
 
@@ -84,8 +107,53 @@ impl<'a> Base85Encoder<'a> {
     }
 }
 
+impl ByteSink for Base85Encoder<'_> {
+    // #[inline(never)] slows this down
+    fn write(&mut self, byte: u8) -> Result<(), EncodeError> {
+        // Fill next buffer slot. If buffer isn't full yet, we're done
+        self.buffer[self.buffer_i] = byte;
+        self.buffer_i += 1;
+        if self.buffer_i == 4 {
+            self.flush_buffer()?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the characters that [`Base85Encoder`] would write, without doing any base85 arithmetic
+///
+/// Every group of four bytes takes five characters, or a single `z` if all of them are zero. The
+/// last group takes one character more than it has bytes.
+#[derive(Default)]
+struct Base85Len {
+    len: usize,
+    group_len: usize,
+    group_is_zero: bool,
+}
+
+impl Base85Len {
+    fn finish(self) -> usize {
+        match self.group_len {
+            0 => self.len,
+            group_len => self.len + group_len + 1,
+        }
+    }
+}
+
+impl ByteSink for Base85Len {
+    fn write(&mut self, byte: u8) -> Result<(), EncodeError> {
+        self.group_is_zero = (self.group_len == 0 || self.group_is_zero) && byte == 0;
+        self.group_len += 1;
+        if self.group_len == 4 {
+            self.len += if self.group_is_zero { 1 } else { 5 };
+            self.group_len = 0;
+        }
+        Ok(())
+    }
+}
+
 // #[inline(never)] slows this down
-fn encode_varint(writer: &mut Base85Encoder<'_>, mut n: u64) -> Result<(), EncodeError> {
+fn encode_varint(writer: &mut impl ByteSink, mut n: u64) -> Result<(), EncodeError> {
     loop {
         let byte = n as u8 & 0x7F;
         n >>= 7;
@@ -100,7 +168,7 @@ fn encode_varint(writer: &mut Base85Encoder<'_>, mut n: u64) -> Result<(), Encod
     Ok(())
 }
 
-fn encode_f64(writer: &mut Base85Encoder<'_>, n: f64) -> Result<(), EncodeError> {
+fn encode_f64(writer: &mut impl ByteSink, n: f64) -> Result<(), EncodeError> {
     for byte in n.to_le_bytes().iter().copied() {
         writer.write(byte)?;
     }
@@ -108,7 +176,7 @@ fn encode_f64(writer: &mut Base85Encoder<'_>, n: f64) -> Result<(), EncodeError>
     Ok(())
 }
 
-fn encode_u32(writer: &mut Base85Encoder<'_>, n: u32) -> Result<(), EncodeError> {
+fn encode_u32(writer: &mut impl ByteSink, n: u32) -> Result<(), EncodeError> {
     for byte in n.to_le_bytes().iter().copied() {
         writer.write(byte)?;
     }
@@ -116,38 +184,73 @@ fn encode_u32(writer: &mut Base85Encoder<'_>, n: u32) -> Result<(), EncodeError>
     Ok(())
 }
 
-fn encode_notes<P: PartialOrd + Copy>(
-    notes: &[Note<P>],
-    time_based: bool,
-    position_decode: impl Fn(&mut Base85Encoder<'_>, P) -> Result<(), EncodeError>,
-) -> Result<String, EncodeError> {
+fn encode_position<R: Sealed>(writer: &mut impl ByteSink, pos: R) -> Result<(), EncodeError> {
+    if R::TIME_BASED {
+        encode_f64(writer, f64::from_bits(pos.to_bits()))
+    } else {
+        encode_varint(writer, encode_row(Row(pos.to_bits()))? as u64)
+    }
+}
+
+fn encode_row(row: Row) -> Result<u32, EncodeError> {
+    row.to_u32().ok_or(EncodeError::RowOutOfRange { row })
+}
+
+const NOTES_PREFIX: &str = "ArrowVortex:notes:";
+const TEMPO_PREFIX: &str = "ArrowVortex:tempo:";
+
+fn check_notes_sorted<P: NotePosition>(notes: &[Note<P>]) -> Result<(), EncodeError> {
     let is_sorted = notes
         .windows(2)
-        .all(|w| (w[0].pos, w[0].column) <= (w[1].pos, w[1].column));
+        .all(|w| (w[0].pos.to_raw(), w[0].column) <= (w[1].pos.to_raw(), w[1].column));
     if !is_sorted {
         return Err(EncodeError::NotSorted);
     }
+    Ok(())
+}
+
+fn write_notes<P: NotePosition>(
+    output: &mut dyn core::fmt::Write,
+    notes: &[Note<P>],
+) -> Result<(), EncodeError> {
+    check_notes_sorted(notes)?;
 
-    let mut buffer = String::from("ArrowVortex:notes:");
-    let mut writer = Base85Encoder::new(&mut buffer);
+    output.write_str(NOTES_PREFIX).map_err(EncodeError::Write)?;
+    let mut writer = Base85Encoder::new(output);
+    encode_notes_body(&mut writer, notes)?;
+    writer.flush_buffer()
+}
 
-    writer.write(time_based as u8)?;
-    encode_varint(&mut writer, notes.len() as u64)?;
+/// Writes the bytes of the notes, which come after the prefix
+fn encode_notes_body<P: NotePosition>(
+    writer: &mut impl ByteSink,
+    notes: &[Note<P>],
+) -> Result<(), EncodeError> {
+    writer.write(P::Raw::TIME_BASED as u8)?;
+    encode_varint(writer, notes.len() as u64)?;
     for note in notes {
-        match note.kind {
+        // The top bit of the column byte marks notes with an end position
+        if note.column >= 0x80 {
+            return Err(EncodeError::ColumnOutOfRange {
+                column: note.column,
+                num_columns: 0x80,
+            });
+        }
+        let pos = note.pos.to_raw();
+        match &note.kind {
             NoteKind::Tap => {
-                writer.write(note.column & 0x7F)?;
-                position_decode(&mut writer, note.pos)?;
+                writer.write(note.column)?;
+                encode_position(writer, pos)?;
             }
             NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => {
                 writer.write(note.column | 0x80)?;
-                position_decode(&mut writer, note.pos)?;
-                position_decode(&mut writer, end_pos)?;
+                encode_position(writer, pos)?;
+                encode_position(writer, end_pos.to_raw())?;
             }
             NoteKind::Mine | NoteKind::Lift | NoteKind::Fake => {
                 writer.write(note.column | 0x80)?;
-                position_decode(&mut writer, note.pos)?;
-                position_decode(&mut writer, note.pos)?;
+                encode_position(writer, pos)?;
+                encode_position(writer, pos)?;
             }
         }
 
@@ -160,10 +263,7 @@ fn encode_notes<P: PartialOrd + Copy>(
             NoteKind::Fake => writer.write(4)?,
         };
     }
-
-    writer.flush_buffer()?;
-
-    Ok(buffer)
+    Ok(())
 }
 
 /// Encodes a list of row-based [`Note`]s into the given writer
@@ -171,13 +271,13 @@ fn encode_notes<P: PartialOrd + Copy>(
 /// Notes should be sorted by row and column to be pastable into ArrowVortex.
 ///
 /// ```rust
-/// use arrowvortex_clipboard::{Note, NoteKind};
+/// use arrowvortex_clipboard::{Note, NoteKind, Row};
 ///
 /// let notes = &[
-///     Note { pos: 0, column: 0, kind: NoteKind::Tap },
-///     Note { pos: 12, column: 1, kind: NoteKind::Tap },
-///     Note { pos: 24, column: 2, kind: NoteKind::Tap },
-///     Note { pos: 36, column: 3, kind: NoteKind::Tap },
+///     Note { pos: Row(0), column: 0, kind: NoteKind::Tap },
+///     Note { pos: Row(12), column: 1, kind: NoteKind::Tap },
+///     Note { pos: Row(24), column: 2, kind: NoteKind::Tap },
+///     Note { pos: Row(36), column: 3, kind: NoteKind::Tap },
 /// ];
 ///
 /// assert_eq!(
@@ -187,8 +287,10 @@ fn encode_notes<P: PartialOrd + Copy>(
 ///
 /// # Ok::<(), arrowvortex_clipboard::EncodeError>(())
 /// ```
-pub fn encode_row_based_notes(notes: &[Note<u64>]) -> Result<String, EncodeError> {
-    encode_notes(notes, false, encode_varint)
+pub fn encode_row_based_notes(notes: &[Note<Row>]) -> Result<String, EncodeError> {
+    let mut buffer = String::new();
+    write_notes(&mut buffer, notes)?;
+    Ok(buffer)
 }
 
 /// Encodes a list of time-based [`Note`]s into the given writer
@@ -213,10 +315,23 @@ pub fn encode_row_based_notes(notes: &[Note<u64>]) -> Result<String, EncodeError
 /// # Ok::<(), arrowvortex_clipboard::EncodeError>(())
 /// ```
 pub fn encode_time_based_notes(notes: &[Note<f64>]) -> Result<String, EncodeError> {
-    encode_notes(notes, true, encode_f64)
+    let mut buffer = String::new();
+    write_notes(&mut buffer, notes)?;
+    Ok(buffer)
+}
+
+/// Encodes a list of [`Note`]s with a custom position type
+///
+/// Row-based or time-based notes are written depending on the position's
+/// [raw representation](NotePosition::Raw). Notes should be sorted by position and column to be
+/// pastable into ArrowVortex. See [`NotePosition`] for an example.
+pub fn encode_notes<P: NotePosition>(notes: &[Note<P>]) -> Result<String, EncodeError> {
+    let mut buffer = String::new();
+    write_notes(&mut buffer, notes)?;
+    Ok(buffer)
 }
 
-fn tempo_event_kind(event: &TempoEventKind) -> u8 {
+pub(crate) fn tempo_event_kind(event: &TempoEventKind) -> u8 {
     match event {
         TempoEventKind::Bpm { .. } => 0,
         TempoEventKind::Stop { .. } => 1,
@@ -235,9 +350,9 @@ fn tempo_event_kind(event: &TempoEventKind) -> u8 {
 fn group_by<'a, T, K: PartialEq>(
     mut slice: &'a [T],
     key: impl Fn(&T) -> K + 'a,
-) -> impl Iterator<Item = (K, &[T])> + 'a {
+) -> impl Iterator<Item = (K, &'a [T])> + 'a {
     core::iter::from_fn(move || {
-        let group_key = key(slice.get(0)?);
+        let group_key = key(slice.first()?);
         let group_end = slice
             .iter()
             .position(|x| key(x) != group_key)
@@ -249,10 +364,10 @@ fn group_by<'a, T, K: PartialEq>(
 }
 
 fn encode_single_tempo_event(
-    writer: &mut Base85Encoder,
+    writer: &mut impl ByteSink,
     event: &TempoEvent,
 ) -> Result<(), EncodeError> {
-    encode_u32(writer, event.row)?;
+    encode_u32(writer, encode_row(event.row)?)?;
     match &event.kind {
         &TempoEventKind::Bpm { bpm } => {
             encode_f64(writer, bpm)?;
@@ -314,13 +429,13 @@ fn encode_single_tempo_event(
 /// Events should be sorted by type and time to be pastable into ArrowVortex.
 ///
 /// ```rust
-/// use arrowvortex_clipboard::{TempoEvent, TempoEventKind};
+/// use arrowvortex_clipboard::{Row, TempoEvent, TempoEventKind};
 ///
 /// let events = &[
-///     TempoEvent { row: 0, kind: TempoEventKind::Bpm { bpm: 120.0 } },
-///     TempoEvent { row: 48, kind: TempoEventKind::Delay { time: 0.2 } },
-///     TempoEvent { row: 96, kind: TempoEventKind::Warp { num_skipped_rows: 24 } },
-///     TempoEvent { row: 144, kind: TempoEventKind::Scroll { ratio: 2.0 } },
+///     TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 120.0 } },
+///     TempoEvent { row: Row(48), kind: TempoEventKind::Delay { time: 0.2 } },
+///     TempoEvent { row: Row(96), kind: TempoEventKind::Warp { num_skipped_rows: 24 } },
+///     TempoEvent { row: Row(144), kind: TempoEventKind::Scroll { ratio: 2.0 } },
 /// ];
 ///
 /// assert_eq!(
@@ -331,28 +446,153 @@ fn encode_single_tempo_event(
 /// # Ok::<(), arrowvortex_clipboard::EncodeError>(())
 /// ```
 pub fn encode_tempo(tempo_events: &[TempoEvent]) -> Result<String, EncodeError> {
+    let mut buffer = String::new();
+    write_tempo(&mut buffer, tempo_events)?;
+    Ok(buffer)
+}
+
+fn check_tempo_sorted(tempo_events: &[TempoEvent]) -> Result<(), EncodeError> {
     let is_sorted = tempo_events.windows(2).all(|w| {
         (tempo_event_kind(&w[0].kind), w[0].row) <= (tempo_event_kind(&w[1].kind), w[1].row)
     });
     if !is_sorted {
         return Err(EncodeError::NotSorted);
     }
+    Ok(())
+}
 
-    let mut buffer = String::from("ArrowVortex:tempo:");
-    let mut writer = Base85Encoder::new(&mut buffer);
+fn write_tempo(
+    output: &mut dyn core::fmt::Write,
+    tempo_events: &[TempoEvent],
+) -> Result<(), EncodeError> {
+    check_tempo_sorted(tempo_events)?;
 
+    output.write_str(TEMPO_PREFIX).map_err(EncodeError::Write)?;
+    let mut writer = Base85Encoder::new(output);
+    encode_tempo_body(&mut writer, tempo_events)?;
+    writer.flush_buffer()
+}
+
+/// Writes the bytes of the tempo events, which come after the prefix
+fn encode_tempo_body(
+    writer: &mut impl ByteSink,
+    tempo_events: &[TempoEvent],
+) -> Result<(), EncodeError> {
     for (kind, events) in group_by(tempo_events, |ev| tempo_event_kind(&ev.kind)) {
-        encode_varint(&mut writer, events.len() as u64)?;
+        encode_varint(writer, events.len() as u64)?;
         writer.write(kind)?;
         for event in events {
-            encode_single_tempo_event(&mut writer, event)?;
+            encode_single_tempo_event(writer, event)?;
         }
     }
-    encode_varint(&mut writer, 0)?; // Empty count signifies end of tempo events list
+    encode_varint(writer, 0) // Empty count signifies end of tempo events list
+}
+
+/// Data to be encoded by [`encode_to_writer`], [`encode_to_slice`] and [`encoded_len`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodeInput<'a> {
+    /// Row based notes, like [`encode_row_based_notes`]
+    RowBasedNotes(&'a [Note<Row>]),
+    /// Time based notes, like [`encode_time_based_notes`]
+    TimeBasedNotes(&'a [Note<f64>]),
+    /// Tempo events, like [`encode_tempo`]
+    TempoEvents(&'a [TempoEvent]),
+}
+
+/// Encodes data into the given writer, without allocating
+///
+/// The output is the same as that of [`encode_row_based_notes`], [`encode_time_based_notes`] and
+/// [`encode_tempo`]. Use this to write into fixed-capacity strings, like those of `heapless`.
+pub fn encode_to_writer(
+    input: EncodeInput<'_>,
+    writer: &mut dyn core::fmt::Write,
+) -> Result<(), EncodeError> {
+    match input {
+        EncodeInput::RowBasedNotes(notes) => write_notes(writer, notes),
+        EncodeInput::TimeBasedNotes(notes) => write_notes(writer, notes),
+        EncodeInput::TempoEvents(tempo_events) => write_tempo(writer, tempo_events),
+    }
+}
 
-    writer.flush_buffer()?;
+/// Writes into a byte slice, counting the bytes that didn't fit instead of failing
+struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
 
-    Ok(buffer)
+impl core::fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        if let Some(target) = self.buffer.get_mut(self.len..end) {
+            target.copy_from_slice(s.as_bytes());
+        }
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Encodes data into the given buffer, without allocating, and returns the number of bytes
+/// written
+///
+/// If the buffer is too small, [`EncodeError::BufferTooSmall`] tells how large it needs to be. The
+/// contents of the buffer are unspecified in that case. See also [`encoded_len`].
+///
+/// ```rust
+/// use arrowvortex_clipboard::{EncodeError, EncodeInput, Note, NoteKind, Row};
+///
+/// let notes = &[
+///     Note { pos: Row(0), column: 0, kind: NoteKind::Tap },
+///     Note { pos: Row(12), column: 1, kind: NoteKind::Tap },
+///     Note { pos: Row(24), column: 2, kind: NoteKind::Tap },
+///     Note { pos: Row(36), column: 3, kind: NoteKind::Tap },
+/// ];
+/// let input = EncodeInput::RowBasedNotes(notes);
+///
+/// let mut buffer = [0; 64];
+/// let len = arrowvortex_clipboard::encode_to_slice(input, &mut buffer)?;
+/// assert_eq!(&buffer[..len], br#"ArrowVortex:notes:!!E9%!=T#H"!d"#);
+///
+/// let result = arrowvortex_clipboard::encode_to_slice(input, &mut [0; 16]);
+/// assert!(matches!(result, Err(EncodeError::BufferTooSmall { required: 31 })));
+/// # Ok::<(), arrowvortex_clipboard::EncodeError>(())
+/// ```
+pub fn encode_to_slice(input: EncodeInput<'_>, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut writer = SliceWriter { buffer, len: 0 };
+    encode_to_writer(input, &mut writer)?;
+    if writer.len > writer.buffer.len() {
+        return Err(EncodeError::BufferTooSmall {
+            required: writer.len,
+        });
+    }
+    Ok(writer.len)
+}
+
+/// Returns the exact length in bytes of the encoded data, without allocating
+///
+/// The length is added up from the number of bytes in each base85 group, without any base85
+/// arithmetic or output. Only groups of four zero bytes, which base85 shortens to a single
+/// character, depend on the values themselves. Fails like the encoding functions if the input is
+/// not sorted or out of range.
+pub fn encoded_len(input: EncodeInput<'_>) -> Result<usize, EncodeError> {
+    let mut counter = Base85Len::default();
+    let prefix = match input {
+        EncodeInput::RowBasedNotes(notes) => {
+            check_notes_sorted(notes)?;
+            encode_notes_body(&mut counter, notes)?;
+            NOTES_PREFIX
+        }
+        EncodeInput::TimeBasedNotes(notes) => {
+            check_notes_sorted(notes)?;
+            encode_notes_body(&mut counter, notes)?;
+            NOTES_PREFIX
+        }
+        EncodeInput::TempoEvents(tempo_events) => {
+            check_tempo_sorted(tempo_events)?;
+            encode_tempo_body(&mut counter, tempo_events)?;
+            TEMPO_PREFIX
+        }
+    };
+    Ok(prefix.len() + counter.finish())
 }
 
 #[cfg(test)]
@@ -390,4 +630,101 @@ mod tests {
 
         assert_eq!(result, expected_result);
     }
+
+    #[test]
+    fn test_encoded_len() {
+        let tempo_events = [
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 150.0 },
+            },
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Label {
+                    message: b"drop".to_vec(),
+                },
+            },
+        ];
+        let notes = crate::text::parse_notes("0 2100\n48 3001\n60 0M0L\n").unwrap();
+        let time_based_notes = notes
+            .iter()
+            .map(|note| Note {
+                pos: note.pos.0 as f64 / 96.0,
+                column: note.column,
+                kind: match note.kind {
+                    NoteKind::Hold { end_pos } => NoteKind::Hold {
+                        end_pos: end_pos.0 as f64 / 96.0,
+                    },
+                    _ => NoteKind::Tap,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        for (input, expected) in &[
+            (
+                EncodeInput::TempoEvents(&tempo_events),
+                encode_tempo(&tempo_events).unwrap(),
+            ),
+            (
+                EncodeInput::RowBasedNotes(&notes),
+                encode_row_based_notes(&notes).unwrap(),
+            ),
+            (
+                EncodeInput::RowBasedNotes(&notes[..1]),
+                encode_row_based_notes(&notes[..1]).unwrap(),
+            ),
+            (EncodeInput::TempoEvents(&[]), encode_tempo(&[]).unwrap()),
+            (
+                EncodeInput::TimeBasedNotes(&time_based_notes),
+                encode_time_based_notes(&time_based_notes).unwrap(),
+            ),
+        ] {
+            assert_eq!(encoded_len(*input).unwrap(), expected.len());
+            let mut buffer = vec![0; expected.len()];
+            assert_eq!(
+                encode_to_slice(*input, &mut buffer).unwrap(),
+                expected.len()
+            );
+            assert_eq!(buffer, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_row_out_of_range() {
+        let beyond = Row(Row::MAX.0 + 1);
+        let notes = [Note {
+            pos: Row::MAX,
+            column: 0,
+            kind: NoteKind::Hold { end_pos: beyond },
+        }];
+        assert!(matches!(
+            encode_row_based_notes(&notes),
+            Err(EncodeError::RowOutOfRange { row }) if row == beyond
+        ));
+
+        let tempo_events = [TempoEvent {
+            row: beyond,
+            kind: TempoEventKind::Scroll { ratio: 1.0 },
+        }];
+        assert!(matches!(
+            encode_tempo(&tempo_events),
+            Err(EncodeError::RowOutOfRange { row }) if row == beyond
+        ));
+    }
+
+    #[test]
+    fn test_column_out_of_range() {
+        let notes = [Note {
+            pos: Row(0),
+            column: 128,
+            kind: NoteKind::Tap,
+        }];
+        assert!(matches!(
+            encode_row_based_notes(&notes),
+            Err(EncodeError::ColumnOutOfRange {
+                column: 128,
+                num_columns: 128
+            })
+        ));
+    }
 }
@@ -0,0 +1,492 @@
+/*!
+Assignment of feet to notes, for pad games played with the feet
+
+[`solve`] finds the most comfortable way to step a selection, using dynamic programming over the
+positions of both feet. Every row is scored by how far the feet travel, and doublesteps,
+footswitches, crossovers and brackets are penalized. The cheapest path through the whole
+selection wins; its awkward steps are reported as [`FootingIssue`]s.
+
+The model doesn't look at timing, so it cannot tell whether a jack is too fast to be hit with one
+foot.
+
+```rust
+use arrowvortex_clipboard::{footing::{self, Foot, IssueKind, Layout}, text, Row};
+
+// Left, down, right: the left foot has to cross over to the right panel
+let notes = text::parse_notes("0 1000\n12 0100\n24 0001")?;
+let footing = footing::solve(&notes, Layout::DanceSingle);
+
+assert_eq!(footing.feet, [Some(Foot::Left), Some(Foot::Right), Some(Foot::Left)]);
+assert_eq!(footing.issues[0].kind, IssueKind::Crossover);
+assert_eq!(footing.issues[0].row, Row(24));
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::mode::GameMode;
+use crate::{Note, NoteKind, Row};
+use alloc::{vec, vec::Vec};
+
+/// Pad layout that the notes are played on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// One pad with left, down, up and right panels
+    DanceSingle,
+    /// Two dance-single pads next to each other
+    DanceDouble,
+}
+
+impl Layout {
+    /// Layout that the given game mode is played on, or `None` for modes other than dance-single
+    /// and dance-double
+    pub fn from_mode(mode: GameMode) -> Option<Self> {
+        match mode {
+            GameMode::DanceSingle => Some(Self::DanceSingle),
+            GameMode::DanceDouble => Some(Self::DanceDouble),
+            _ => None,
+        }
+    }
+
+    fn num_panels(self) -> u8 {
+        match self {
+            Self::DanceSingle => 4,
+            Self::DanceDouble => 8,
+        }
+    }
+
+    /// Center of the given panel, with the x axis pointing right and the y axis pointing down
+    fn panel_position(self, panel: u8) -> (f64, f64) {
+        let (x, y) = match panel % 4 {
+            0 => (0.0, 1.0),
+            1 => (1.0, 2.0),
+            2 => (1.0, 0.0),
+            _ => (2.0, 1.0),
+        };
+        (x + 3.0 * (panel / 4) as f64, y)
+    }
+
+    fn start_state(self) -> (u16, u16) {
+        match self {
+            Self::DanceSingle => (1 << 0, 1 << 3),
+            Self::DanceDouble => (1 << 3, 1 << 4),
+        }
+    }
+}
+
+/// Left or right foot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Foot {
+    /// Left foot
+    Left,
+    /// Right foot
+    Right,
+}
+
+/// Type of a [`FootingIssue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IssueKind {
+    /// The same foot steps twice in a row onto different panels, while the other foot is free
+    Doublestep,
+    /// A foot steps onto the panel that the other foot is standing on
+    Footswitch,
+    /// The feet end up crossed, with the left foot to the right of the right foot
+    Crossover,
+    /// The body has to turn around by more than 180 degrees
+    Spin,
+    /// One foot hits two panels at once
+    Bracket,
+    /// The row cannot be hit with two feet at all, for example a chord of more than four notes or
+    /// a note while both feet are busy holding
+    Unplayable,
+}
+
+/// Awkward step found by [`solve`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FootingIssue {
+    /// Row of the step
+    pub row: Row,
+    /// What is awkward about the step
+    pub kind: IssueKind,
+}
+
+/// Result of [`solve`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Footing {
+    /// The foot that hits each note, in the same order as the notes given to [`solve`]. Mines,
+    /// fakes and notes in unplayable rows have no foot
+    pub feet: Vec<Option<Foot>>,
+    /// Awkward steps along the chosen path, sorted by row
+    pub issues: Vec<FootingIssue>,
+}
+
+const MOVE_COST: f64 = 1.0;
+const JACK_COST: f64 = 1.0;
+const BRACKET_COST: f64 = 3.0;
+const FOOTSWITCH_COST: f64 = 3.0;
+const CROSSOVER_COST: f64 = 3.0;
+const DOUBLESTEP_COST: f64 = 8.0;
+
+/// Position and occupation of both feet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct State {
+    /// Panels under each foot, as bitmasks
+    panels: [u16; 2],
+    /// Row at which each foot's hold or roll ends, or zero
+    hold_ends: [u64; 2],
+    /// Which feet stepped on the previous row, as bitmask
+    last_stepped: u8,
+}
+
+struct Entry {
+    state: State,
+    cost: f64,
+    prev: usize,
+    /// Foot for each column of the row, as bitmask of the columns stepped with the right foot
+    right_columns: u32,
+    issues: Vec<IssueKind>,
+}
+
+struct NoteRow {
+    pos: u64,
+    /// Column, hold end and note index of every note on this row
+    notes: Vec<(u8, Option<u64>, usize)>,
+}
+
+fn centroid(layout: Layout, panels: u16) -> (f64, f64) {
+    let mut sum = (0.0, 0.0);
+    let mut count = 0.0;
+    for panel in 0..16 {
+        if panels & (1 << panel) != 0 {
+            let (x, y) = layout.panel_position(panel);
+            sum = (sum.0 + x, sum.1 + y);
+            count += 1.0;
+        }
+    }
+    (sum.0 / count, sum.1 / count)
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Angle in degrees of the line from the left to the right foot; 0 when facing forward
+fn facing_angle(layout: Layout, panels: [u16; 2]) -> f64 {
+    let (left, right) = (centroid(layout, panels[0]), centroid(layout, panels[1]));
+    (right.1 - left.1).atan2(right.0 - left.0).to_degrees()
+}
+
+fn is_bracketable(layout: Layout, panels: u16) -> bool {
+    match panels.count_ones() {
+        1 => true,
+        2 => {
+            let first = panels.trailing_zeros() as u8;
+            let second = 15 - panels.leading_zeros() as u8;
+            distance(layout.panel_position(first), layout.panel_position(second)) < 1.5
+        }
+        _ => false,
+    }
+}
+
+fn transition(
+    layout: Layout,
+    row: &NoteRow,
+    prev: &State,
+    right_columns: u32,
+) -> Option<(State, f64, Vec<IssueKind>)> {
+    let mut state = *prev;
+    let mut cost = 0.0;
+    let mut issues = Vec::new();
+    let mut stepped = 0;
+
+    for (foot, is_right) in [(0, false), (1, true)].iter().copied() {
+        let mut panels = 0_u16;
+        let mut hold_end = 0;
+        for (i, &(column, note_hold_end, _)) in row.notes.iter().enumerate() {
+            if (right_columns & (1 << i) != 0) == is_right {
+                panels |= 1 << column;
+                hold_end = hold_end.max(note_hold_end.unwrap_or(0));
+            }
+        }
+        if panels == 0 {
+            continue;
+        }
+        if prev.hold_ends[foot] > row.pos || !is_bracketable(layout, panels) {
+            return None;
+        }
+        stepped |= 1 << foot;
+
+        let other = 1 - foot;
+        if panels == prev.panels[foot] {
+            cost += JACK_COST;
+        } else {
+            cost += MOVE_COST
+                * distance(
+                    centroid(layout, prev.panels[foot]),
+                    centroid(layout, panels),
+                );
+        }
+        if panels.count_ones() == 2 {
+            cost += BRACKET_COST;
+            issues.push(IssueKind::Bracket);
+        }
+        if panels & prev.panels[other] != 0 {
+            cost += FOOTSWITCH_COST;
+            issues.push(IssueKind::Footswitch);
+        }
+
+        state.panels[foot] = panels;
+        state.hold_ends[foot] = hold_end;
+    }
+
+    // Jumps are never doublesteps, so only single-foot steps are checked
+    for foot in 0..2 {
+        let other = 1 - foot;
+        let other_is_holding = prev.hold_ends[other] > row.pos;
+        if stepped == 1 << foot
+            && prev.last_stepped == stepped
+            && state.panels[foot] != prev.panels[foot]
+            && !other_is_holding
+        {
+            cost += DOUBLESTEP_COST;
+            issues.push(IssueKind::Doublestep);
+        }
+    }
+
+    let was_crossed = facing_angle(layout, prev.panels).abs() > 90.0;
+    if facing_angle(layout, state.panels).abs() > 90.0 {
+        cost += CROSSOVER_COST;
+        if !was_crossed {
+            issues.push(IssueKind::Crossover);
+        }
+    }
+
+    state.last_stepped = stepped;
+    for hold_end in &mut state.hold_ends {
+        if *hold_end <= row.pos {
+            *hold_end = 0;
+        }
+    }
+    Some((state, cost, issues))
+}
+
+/// Assigns a foot to every steppable note and reports awkward steps
+///
+/// Notes don't need to be sorted. Columns outside the layout are treated as unplayable.
+pub fn solve(notes: &[Note<Row>], layout: Layout) -> Footing {
+    let mut rows = alloc::collections::BTreeMap::<u64, NoteRow>::new();
+    for (i, note) in notes.iter().enumerate() {
+        let hold_end = match note.kind {
+            NoteKind::Mine | NoteKind::Fake => continue,
+            NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => Some(end_pos.0),
+            NoteKind::Tap | NoteKind::Lift => None,
+        };
+        let row = rows.entry(note.pos.0).or_insert_with(|| NoteRow {
+            pos: note.pos.0,
+            notes: Vec::new(),
+        });
+        row.notes.push((note.column, hold_end, i));
+    }
+
+    let (left, right) = layout.start_state();
+    let start = State {
+        panels: [left, right],
+        hold_ends: [0, 0],
+        last_stepped: 0,
+    };
+    let mut layers = vec![vec![Entry {
+        state: start,
+        cost: 0.0,
+        prev: 0,
+        right_columns: 0,
+        issues: Vec::new(),
+    }]];
+    let mut unplayable_rows = Vec::new();
+
+    for row in rows.values() {
+        let prev_layer = layers.last().unwrap();
+        let mut layer: Vec<Entry> = Vec::new();
+        let mut layer_index = alloc::collections::BTreeMap::new();
+
+        let is_in_layout = row
+            .notes
+            .iter()
+            .all(|&(column, _, _)| column < layout.num_panels());
+        if is_in_layout && row.notes.len() <= 4 {
+            for (prev_index, prev) in prev_layer.iter().enumerate() {
+                for right_columns in 0..(1_u32 << row.notes.len()) {
+                    let (state, cost, issues) =
+                        match transition(layout, row, &prev.state, right_columns) {
+                            Some(transition) => transition,
+                            None => continue,
+                        };
+                    let entry = Entry {
+                        state,
+                        cost: prev.cost + cost,
+                        prev: prev_index,
+                        right_columns,
+                        issues,
+                    };
+                    match layer_index.get(&state) {
+                        Some(&i) => {
+                            let existing: &mut Entry = &mut layer[i];
+                            if entry.cost < existing.cost {
+                                *existing = entry;
+                            }
+                        }
+                        None => {
+                            layer_index.insert(state, layer.len());
+                            layer.push(entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        if layer.is_empty() {
+            // Nobody can hit this row with their feet; skip it without moving
+            unplayable_rows.push(row.pos);
+            layer = prev_layer
+                .iter()
+                .enumerate()
+                .map(|(prev_index, prev)| Entry {
+                    state: prev.state,
+                    cost: prev.cost,
+                    prev: prev_index,
+                    right_columns: u32::MAX,
+                    issues: vec![IssueKind::Unplayable],
+                })
+                .collect();
+        }
+        layers.push(layer);
+    }
+
+    // Walk back from the cheapest final state
+    let mut footing = Footing {
+        feet: vec![None; notes.len()],
+        issues: Vec::new(),
+    };
+    let mut index = layers
+        .last()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.cost.partial_cmp(&b.cost).unwrap())
+        .map_or(0, |(i, _)| i);
+    let mut path = Vec::new();
+    for (layer, row) in layers[1..].iter().zip(rows.values()).rev() {
+        let entry = &layer[index];
+        if entry.right_columns != u32::MAX {
+            for (i, &(_, _, note_index)) in row.notes.iter().enumerate() {
+                footing.feet[note_index] = Some(if entry.right_columns & (1 << i) != 0 {
+                    Foot::Right
+                } else {
+                    Foot::Left
+                });
+            }
+        }
+        for &kind in &entry.issues {
+            footing.issues.push(FootingIssue {
+                row: Row(row.pos),
+                kind,
+            });
+        }
+        path.push((row.pos, entry.state));
+        index = entry.prev;
+    }
+    path.reverse();
+
+    // Spins can only be seen by following the body's rotation along the whole path
+    let mut angle = facing_angle(layout, start.panels);
+    let mut total_angle = 0.0;
+    let mut is_spinning = false;
+    for (row, state) in path {
+        let new_angle = facing_angle(layout, state.panels);
+        let mut delta = new_angle - angle;
+        if delta > 180.0 {
+            delta -= 360.0;
+        } else if delta <= -180.0 {
+            delta += 360.0;
+        }
+        total_angle += delta;
+        angle = new_angle;
+
+        if f64::abs(total_angle) > 180.0 + 1e-9 && !is_spinning {
+            footing.issues.push(FootingIssue {
+                row: Row(row),
+                kind: IssueKind::Spin,
+            });
+            is_spinning = true;
+        } else if f64::abs(total_angle) <= 90.0 {
+            is_spinning = false;
+        }
+    }
+
+    footing.issues.sort();
+    footing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_text(text: &str, layout: Layout) -> Footing {
+        solve(&crate::text::parse_notes(text).unwrap(), layout)
+    }
+
+    fn issue_kinds(footing: &Footing) -> Vec<IssueKind> {
+        footing.issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn test_alternating() {
+        let footing = solve_text(
+            "0 1000\n12 0001\n24 0100\n36 0010\n48 1001",
+            Layout::DanceSingle,
+        );
+        assert_eq!(
+            footing.feet,
+            [
+                Some(Foot::Left),
+                Some(Foot::Right),
+                Some(Foot::Left),
+                Some(Foot::Right),
+                Some(Foot::Left),
+                Some(Foot::Right),
+            ]
+        );
+        assert_eq!(footing.issues, []);
+    }
+
+    #[test]
+    fn test_issues() {
+        // Hold on the left panel forces the right foot to hit everything else
+        let footing = solve_text("0 2000\n12 0100\n24 0001\n36 3000", Layout::DanceSingle);
+        assert_eq!(footing.feet[1..], [Some(Foot::Right), Some(Foot::Right)]);
+        assert!(footing.issues.is_empty());
+
+        let footing = solve_text("0 1100", Layout::DanceSingle);
+        assert_eq!(issue_kinds(&footing), []);
+        let footing = solve_text("0 1110", Layout::DanceSingle);
+        assert_eq!(issue_kinds(&footing), [IssueKind::Bracket]);
+        let footing = solve_text("0 11111", Layout::DanceSingle);
+        assert_eq!(issue_kinds(&footing), [IssueKind::Unplayable]);
+        assert_eq!(footing.feet, [None; 5]);
+
+        let footing = solve_text("0 2000\n12 0002\n24 0100\n36 3003", Layout::DanceSingle);
+        assert_eq!(issue_kinds(&footing), [IssueKind::Unplayable]);
+    }
+
+    #[test]
+    fn test_spin() {
+        // Left, down, right, up, left, down, right with alternating feet turns the body around
+        let footing = solve_text(
+            "0 1000\n12 0100\n24 0001\n36 0010\n48 1000\n60 0100\n72 0001",
+            Layout::DanceSingle,
+        );
+        assert!(issue_kinds(&footing).contains(&IssueKind::Crossover));
+        assert!(issue_kinds(&footing).contains(&IssueKind::Spin));
+    }
+}
@@ -0,0 +1,227 @@
+/*!
+[`Arbitrary`] implementations for fuzzing (requires the `arbitrary` feature)
+
+The generated notes and tempo events are always valid, i.e. encoding and decoding them gives back
+the same values. Rows are at most [`Row::MAX`], notes have columns below 128, holds and rolls end
+after they start, and time-based positions and all floating point values are finite.
+[`SortedNotes`] and [`SortedTempoEvents`] additionally sort and deduplicate their contents the way
+the encoder expects.
+
+```rust
+use arbitrary::{Arbitrary, Unstructured};
+use arrowvortex_clipboard::{fuzzing::SortedNotes, Row};
+
+let mut u = Unstructured::new(&[7, 3, 250, 19, 4, 0, 88, 1, 2, 3, 9, 120, 64, 32, 16, 8]);
+let SortedNotes(notes) = SortedNotes::<Row>::arbitrary(&mut u)?;
+
+let encoded = arrowvortex_clipboard::encode_row_based_notes(&notes).unwrap();
+match arrowvortex_clipboard::decode(encoded.as_bytes()).unwrap() {
+    arrowvortex_clipboard::DecodeResult::RowBasedNotes(decoded) => assert_eq!(decoded, notes),
+    _ => unreachable!(),
+}
+# Ok::<(), arbitrary::Error>(())
+```
+*/
+
+use crate::encode::tempo_event_kind;
+use crate::{Note, NoteKind, Row, TempoEvent, TempoEventKind};
+use alloc::vec::Vec;
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// Note positions that can be generated
+trait Position: Copy + PartialOrd {
+    fn arbitrary_start(u: &mut Unstructured<'_>) -> Result<Self>;
+    /// End position of a hold or roll starting at `start`
+    fn arbitrary_end(u: &mut Unstructured<'_>, start: Self) -> Result<Self>;
+}
+
+impl Position for Row {
+    fn arbitrary_start(u: &mut Unstructured<'_>) -> Result<Self> {
+        Ok(Row(u.int_in_range(0..=Row::MAX.0 - 1)?))
+    }
+
+    fn arbitrary_end(u: &mut Unstructured<'_>, start: Self) -> Result<Self> {
+        Ok(Row(
+            (start.0 + u.int_in_range(1..=u16::MAX as u64)?).min(Row::MAX.0)
+        ))
+    }
+}
+
+impl Position for f64 {
+    fn arbitrary_start(u: &mut Unstructured<'_>) -> Result<Self> {
+        Ok(u.arbitrary::<i32>()? as f64 / 1000.0)
+    }
+
+    fn arbitrary_end(u: &mut Unstructured<'_>, start: Self) -> Result<Self> {
+        Ok(start + (u.arbitrary::<u16>()? as f64 + 1.0) / 1000.0)
+    }
+}
+
+fn arbitrary_note<P: Position>(u: &mut Unstructured<'_>) -> Result<Note<P>> {
+    let pos = P::arbitrary_start(u)?;
+    let column = u.int_in_range(0..=127)?;
+    let kind = match u.int_in_range(0..=5)? {
+        0 => NoteKind::Tap,
+        1 => NoteKind::Hold {
+            end_pos: P::arbitrary_end(u, pos)?,
+        },
+        2 => NoteKind::Mine,
+        3 => NoteKind::Roll {
+            end_pos: P::arbitrary_end(u, pos)?,
+        },
+        4 => NoteKind::Lift,
+        _ => NoteKind::Fake,
+    };
+    Ok(Note { pos, column, kind })
+}
+
+fn arbitrary_sorted_notes<P: Position>(u: &mut Unstructured<'_>) -> Result<Vec<Note<P>>> {
+    let mut notes = Vec::new();
+    while !u.is_empty() && u.arbitrary()? {
+        notes.push(arbitrary_note(u)?);
+    }
+    notes.sort_by(|a, b| {
+        (a.pos, a.column)
+            .partial_cmp(&(b.pos, b.column))
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    notes.dedup_by(|a, b| (a.pos, a.column) == (b.pos, b.column));
+    Ok(notes)
+}
+
+impl<'a> Arbitrary<'a> for Note<Row> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_note(u)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Note<f64> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_note(u)
+    }
+}
+
+/// Finite floating point value
+fn finite_f64(u: &mut Unstructured<'_>) -> Result<f64> {
+    let value = u.arbitrary::<f64>()?;
+    Ok(if value.is_finite() { value } else { 0.0 })
+}
+
+impl<'a> Arbitrary<'a> for TempoEventKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=10)? {
+            0 => Self::Bpm {
+                bpm: finite_f64(u)?,
+            },
+            1 => Self::Stop {
+                time: finite_f64(u)?,
+            },
+            2 => Self::Delay {
+                time: finite_f64(u)?,
+            },
+            3 => Self::Warp {
+                num_skipped_rows: u.arbitrary()?,
+            },
+            4 => Self::TimeSignature {
+                numerator: u.arbitrary()?,
+                denominator: u.arbitrary()?,
+            },
+            5 => Self::Ticks {
+                num_ticks: u.arbitrary()?,
+            },
+            6 => Self::Combo {
+                combo_multiplier: u.arbitrary()?,
+                miss_multiplier: u.arbitrary()?,
+            },
+            7 => Self::Speed {
+                ratio: finite_f64(u)?,
+                delay: finite_f64(u)?,
+                delay_is_time: u.arbitrary()?,
+            },
+            8 => Self::Scroll {
+                ratio: finite_f64(u)?,
+            },
+            9 => Self::FakeSegment {
+                num_fake_rows: u.arbitrary()?,
+            },
+            _ => Self::Label {
+                message: u.arbitrary()?,
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Row {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Row(u.int_in_range(0..=Row::MAX.0)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for TempoEvent {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            row: u.arbitrary()?,
+            kind: u.arbitrary()?,
+        })
+    }
+}
+
+/// Notes sorted by position and column, with at most one note per position and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortedNotes<P>(pub Vec<Note<P>>);
+
+impl<'a> Arbitrary<'a> for SortedNotes<Row> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_sorted_notes(u).map(Self)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SortedNotes<f64> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_sorted_notes(u).map(Self)
+    }
+}
+
+/// Tempo events sorted by type and row, with at most one event per type and row
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortedTempoEvents(pub Vec<TempoEvent>);
+
+impl<'a> Arbitrary<'a> for SortedTempoEvents {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut tempo_events = Vec::new();
+        while !u.is_empty() && u.arbitrary()? {
+            tempo_events.push(TempoEvent::arbitrary(u)?);
+        }
+        tempo_events.sort_by_key(|event| (tempo_event_kind(&event.kind), event.row));
+        tempo_events.dedup_by_key(|event| (tempo_event_kind(&event.kind), event.row));
+        Ok(Self(tempo_events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_output() {
+        // Deterministic pseudo random input
+        let data = (0..4096_u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+
+        let SortedTempoEvents(tempo_events) = u.arbitrary().unwrap();
+        let encoded = crate::encode_tempo(&tempo_events).unwrap();
+        match crate::decode(encoded.as_bytes()).unwrap() {
+            crate::DecodeResult::TempoEvents(decoded) => assert_eq!(decoded, tempo_events),
+            _ => panic!("expected tempo events"),
+        }
+
+        let SortedNotes(notes) = SortedNotes::<f64>::arbitrary(&mut u).unwrap();
+        let encoded = crate::encode_time_based_notes(&notes).unwrap();
+        match crate::decode(encoded.as_bytes()).unwrap() {
+            crate::DecodeResult::TimeBasedNotes(decoded) => assert_eq!(decoded, notes),
+            _ => panic!("expected time-based notes"),
+        }
+    }
+}
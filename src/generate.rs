@@ -0,0 +1,292 @@
+/*!
+Generation of practice patterns
+
+[`generate`] produces taps following a [`PatternKind`]. The output is sorted and can be passed to
+[`encode_row_based_notes`](crate::encode_row_based_notes) directly. The same options, including the
+seed, always generate the same notes.
+
+```rust
+use arrowvortex_clipboard::{generate, render::Snap, Row};
+
+let notes = generate::generate(&generate::GeneratorOptions {
+    num_rows: 64,
+    snap: Snap::Sixteenth,
+    pattern: generate::PatternKind::Jumpstream { jump_density: 0.25 },
+    ..Default::default()
+});
+assert_eq!(notes.last().unwrap().pos, Row(63 * 12));
+
+let clipboard_data = arrowvortex_clipboard::encode_row_based_notes(&notes)?;
+# Ok::<(), arrowvortex_clipboard::EncodeError>(())
+```
+*/
+
+use crate::render::Snap;
+use crate::{Note, NoteKind, Row, ROWS_PER_BEAT};
+use alloc::{vec, vec::Vec};
+
+/// Shape of the pattern to generate
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// Single notes without jacks
+    Stream,
+    /// Single notes and jumps without jacks
+    Jumpstream {
+        /// Share of rows that are jumps, from 0 to 1
+        jump_density: f64,
+    },
+    /// Chords that always share at least one column with the previous chord
+    Chordjacks {
+        /// Number of notes in each chord
+        chord_size: u8,
+    },
+    /// Single notes alternating between two columns
+    Trill,
+    /// Single notes sweeping back and forth across all columns
+    Staircase,
+    /// Random chords
+    Random {
+        /// Largest number of notes in one row
+        max_chord_size: u8,
+        /// Whether a row may share columns with the previous row
+        allow_jacks: bool,
+    },
+}
+
+/// Settings for [`generate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorOptions {
+    /// Number of rows with notes to generate
+    pub num_rows: usize,
+    /// Spacing between consecutive rows
+    pub snap: Snap,
+    /// Row of the first generated note
+    pub start_row: Row,
+    /// Number of columns to place notes in, from 1 up to the 128 columns that ArrowVortex clipboard
+    /// data can hold. Values outside that range are clamped
+    pub num_columns: u8,
+    /// Pattern to generate
+    pub pattern: PatternKind,
+    /// Seed for the random number generator
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            num_rows: 64,
+            snap: Snap::Sixteenth,
+            start_row: Row::ZERO,
+            num_columns: 4,
+            pattern: PatternKind::Stream,
+            seed: 0,
+        }
+    }
+}
+
+/// SplitMix64 pseudo random number generator
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed integer in `0..n`. `n` must not be zero
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns true with the given probability
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64) < probability
+    }
+
+    /// Picks `count` distinct elements from `candidates`, or all of them if there are fewer
+    fn pick(&mut self, mut candidates: Vec<u8>, count: usize) -> Vec<u8> {
+        let mut picked = Vec::with_capacity(count);
+        while picked.len() < count && !candidates.is_empty() {
+            let i = self.below(candidates.len());
+            picked.push(candidates.swap_remove(i));
+        }
+        picked
+    }
+}
+
+fn columns_except(num_columns: u8, excluded: &[u8]) -> Vec<u8> {
+    (0..num_columns)
+        .filter(|column| !excluded.contains(column))
+        .collect()
+}
+
+/// Generates `options.num_rows` rows of taps following `options.pattern`
+///
+/// Row `i` is placed at `options.start_row` plus `i` times the spacing of `options.snap`. If a
+/// pattern cannot be generated with the given number of columns, for example a jump without jacks
+/// on two columns, rows get as many notes as possible. Generation stops early at [`Row::MAX`], the
+/// last row ArrowVortex supports.
+pub fn generate(options: &GeneratorOptions) -> Vec<Note<Row>> {
+    let mut rng = Rng::new(options.seed);
+    let num_columns = options.num_columns.clamp(1, 128);
+    let spacing = ROWS_PER_BEAT * 4 / options.snap.denominator() as u64;
+
+    let trill_columns = rng.pick(columns_except(num_columns, &[]), 2);
+    let mut staircase_column = rng.below(num_columns as usize) as i16;
+    let mut staircase_direction = if rng.chance(0.5) { 1 } else { -1 };
+
+    let mut notes = Vec::new();
+    let mut prev_columns: Vec<u8> = Vec::new();
+    for i in 0..options.num_rows {
+        let pos = match (i as u64)
+            .checked_mul(spacing)
+            .and_then(|offset| options.start_row.checked_add(offset))
+        {
+            Some(pos) if pos <= Row::MAX => pos,
+            _ => break,
+        };
+        let mut columns = match options.pattern {
+            PatternKind::Stream => rng.pick(columns_except(num_columns, &prev_columns), 1),
+            PatternKind::Jumpstream { jump_density } => {
+                let size = if rng.chance(jump_density) { 2 } else { 1 };
+                rng.pick(columns_except(num_columns, &prev_columns), size)
+            }
+            PatternKind::Chordjacks { chord_size } => {
+                let chord_size = (chord_size as usize).max(1);
+                let mut columns = rng.pick(prev_columns.clone(), 1);
+                columns.extend(rng.pick(
+                    columns_except(num_columns, &columns),
+                    chord_size - columns.len(),
+                ));
+                columns
+            }
+            PatternKind::Trill => vec![trill_columns[i % trill_columns.len()]],
+            PatternKind::Staircase => {
+                let column = staircase_column;
+                if num_columns > 1 {
+                    let next = column + staircase_direction;
+                    if next < 0 || next >= num_columns as i16 {
+                        staircase_direction = -staircase_direction;
+                    }
+                    staircase_column += staircase_direction;
+                }
+                vec![column as u8]
+            }
+            PatternKind::Random {
+                max_chord_size,
+                allow_jacks,
+            } => {
+                let size = 1 + rng.below((max_chord_size as usize).max(1));
+                if allow_jacks {
+                    rng.pick(columns_except(num_columns, &[]), size)
+                } else {
+                    rng.pick(columns_except(num_columns, &prev_columns), size)
+                }
+            }
+        };
+        columns.sort_unstable();
+
+        notes.extend(columns.iter().map(|&column| Note {
+            pos,
+            column,
+            kind: NoteKind::Tap,
+        }));
+        prev_columns = columns;
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_pattern(pattern: PatternKind) -> Vec<Note<Row>> {
+        generate(&GeneratorOptions {
+            num_rows: 200,
+            pattern,
+            seed: 42,
+            ..Default::default()
+        })
+    }
+
+    fn rows(notes: &[Note<Row>]) -> Vec<Vec<u8>> {
+        let mut rows = alloc::collections::BTreeMap::<u64, Vec<u8>>::new();
+        for note in notes {
+            rows.entry(note.pos.0).or_default().push(note.column);
+        }
+        rows.into_iter().map(|(_, columns)| columns).collect()
+    }
+
+    fn has_jack(a: &[u8], b: &[u8]) -> bool {
+        a.iter().any(|column| b.contains(column))
+    }
+
+    #[test]
+    fn test_patterns() {
+        let stream = rows(&generate_pattern(PatternKind::Stream));
+        assert_eq!(stream.len(), 200);
+        assert!(stream
+            .windows(2)
+            .all(|w| w[0].len() == 1 && !has_jack(&w[0], &w[1])));
+
+        let jumpstream = rows(&generate_pattern(PatternKind::Jumpstream {
+            jump_density: 0.3,
+        }));
+        let num_jumps = jumpstream.iter().filter(|row| row.len() == 2).count();
+        assert!((40..80).contains(&num_jumps));
+        assert!(jumpstream.windows(2).all(|w| !has_jack(&w[0], &w[1])));
+
+        let chordjacks = rows(&generate_pattern(PatternKind::Chordjacks { chord_size: 3 }));
+        assert!(chordjacks.iter().all(|row| row.len() == 3));
+        assert!(chordjacks.windows(2).all(|w| has_jack(&w[0], &w[1])));
+
+        let staircase = rows(&generate_pattern(PatternKind::Staircase));
+        assert!(staircase
+            .windows(2)
+            .all(|w| (w[0][0] as i16 - w[1][0] as i16).abs() == 1));
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let pattern = PatternKind::Random {
+            max_chord_size: 3,
+            allow_jacks: true,
+        };
+        assert_eq!(generate_pattern(pattern.clone()), generate_pattern(pattern));
+        assert!(crate::encode_row_based_notes(&generate_pattern(PatternKind::Trill)).is_ok());
+
+        let wide = generate(&GeneratorOptions {
+            num_columns: u8::MAX,
+            pattern: PatternKind::Staircase,
+            num_rows: 300,
+            ..Default::default()
+        });
+        assert!(wide.iter().all(|note| note.column < 128));
+        assert!(crate::encode_row_based_notes(&wide).is_ok());
+    }
+
+    #[test]
+    fn test_last_row() {
+        let notes = generate(&GeneratorOptions {
+            start_row: Row(Row::MAX.0 - 12),
+            snap: Snap::Sixteenth,
+            ..Default::default()
+        });
+        assert_eq!(rows(&notes).len(), 2);
+        assert!(crate::encode_row_based_notes(&notes).is_ok());
+
+        let notes = generate(&GeneratorOptions {
+            start_row: Row(u64::MAX),
+            num_rows: usize::MAX,
+            ..Default::default()
+        });
+        assert!(notes.is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+/*!
+Editing of holds and rolls
+
+Convert between holds, rolls and taps, shorten holds that run into the next note, force hold lengths
+into a range, and split a selection at a row. Functions that change notes in place keep the order of
+the notes, so sorted input stays sorted.
+
+```rust
+use arrowvortex_clipboard::{holds, text, Row};
+
+let mut notes = text::parse_notes("0 2000\n24 3000\n96 1000")?;
+// Stretching the hold makes it run into the tap, so it is cut back to end half a beat earlier
+holds::extend_to_min_length(&mut notes, 96);
+holds::clamp_to_next_note(&mut notes, 24);
+holds::holds_to_rolls(&mut notes);
+assert_eq!(notes, text::parse_notes("0 4000\n72 3000\n96 1000")?);
+
+let (first, second) = holds::split_at(&notes, Row(48));
+assert_eq!(first, text::parse_notes("0 4000\n48 3000")?);
+assert_eq!(second, text::parse_notes("48 4000\n72 3000\n96 1000")?);
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::{Note, NoteKind, Row};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Turns every hold into a roll of the same length
+pub fn holds_to_rolls<P: Clone>(notes: &mut [Note<P>]) {
+    for note in notes {
+        if let NoteKind::Hold { end_pos } = &note.kind {
+            note.kind = NoteKind::Roll {
+                end_pos: end_pos.clone(),
+            };
+        }
+    }
+}
+
+/// Turns every roll into a hold of the same length
+pub fn rolls_to_holds<P: Clone>(notes: &mut [Note<P>]) {
+    for note in notes {
+        if let NoteKind::Roll { end_pos } = &note.kind {
+            note.kind = NoteKind::Hold {
+                end_pos: end_pos.clone(),
+            };
+        }
+    }
+}
+
+/// Turns every hold and roll into a tap at its head
+pub fn holds_to_taps<P>(notes: &mut [Note<P>]) {
+    for note in notes {
+        if let NoteKind::Hold { .. } | NoteKind::Roll { .. } = note.kind {
+            note.kind = NoteKind::Tap;
+        }
+    }
+}
+
+fn end_pos_mut(note: &mut Note<Row>) -> Option<&mut Row> {
+    match &mut note.kind {
+        NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => Some(end_pos),
+        _ => None,
+    }
+}
+
+/// Turns holds and rolls of zero or negative length into taps
+fn collapse(note: &mut Note<Row>) {
+    let pos = note.pos;
+    if matches!(end_pos_mut(note), Some(end_pos) if *end_pos <= pos) {
+        note.kind = NoteKind::Tap;
+    }
+}
+
+/// Shortens holds and rolls so they end at least `gap` rows before the next note in their column
+///
+/// Holds and rolls that would end at or before their head become taps. Notes may be given in any
+/// order.
+pub fn clamp_to_next_note(notes: &mut [Note<Row>], gap: u64) {
+    let mut positions = BTreeMap::<u8, Vec<Row>>::new();
+    for note in notes.iter() {
+        positions.entry(note.column).or_default().push(note.pos);
+    }
+    for column_positions in positions.values_mut() {
+        column_positions.sort_unstable();
+    }
+
+    for note in notes.iter_mut() {
+        let column_positions = &positions[&note.column];
+        // Index of the first position after this note's
+        let next_index = column_positions
+            .binary_search_by(|&pos| {
+                if pos <= note.pos {
+                    core::cmp::Ordering::Less
+                } else {
+                    core::cmp::Ordering::Greater
+                }
+            })
+            .unwrap_or_else(|index| index);
+        let next_pos = match column_positions.get(next_index) {
+            Some(&next_pos) => next_pos,
+            None => continue,
+        };
+        if let Some(end_pos) = end_pos_mut(note) {
+            let latest_end = Row(next_pos.0.saturating_sub(gap));
+            *end_pos = (*end_pos).min(latest_end);
+        }
+        collapse(note);
+    }
+}
+
+/// Extends holds and rolls shorter than `min_rows` to that length
+///
+/// This may make them run into the next note in their column; call [`clamp_to_next_note`]
+/// afterwards to prevent that. Holds and rolls are not extended past [`Row::MAX`].
+pub fn extend_to_min_length(notes: &mut [Note<Row>], min_rows: u64) {
+    for note in notes {
+        let pos = note.pos;
+        if let Some(end_pos) = end_pos_mut(note) {
+            *end_pos = (*end_pos).max(pos.saturating_add(min_rows));
+        }
+    }
+}
+
+/// Shortens holds and rolls longer than `max_rows` to that length
+///
+/// A maximum of zero turns all holds and rolls into taps.
+pub fn trim_to_max_length(notes: &mut [Note<Row>], max_rows: u64) {
+    for note in notes {
+        let pos = note.pos;
+        if let Some(end_pos) = end_pos_mut(note) {
+            // Without a representable maximum end, the hold can't be too long
+            if let Some(max_end_pos) = pos.checked_add(max_rows) {
+                *end_pos = (*end_pos).min(max_end_pos);
+            }
+        }
+        collapse(note);
+    }
+}
+
+/// Splits the notes into those before `row` and those at or after it
+///
+/// Holds and rolls that cross `row` are cut in two: the first part ends at `row` and the second
+/// part starts there with the remaining length. Both halves keep the order of `notes`, except that
+/// the second parts of cut holds are placed among the notes at `row` by column, so sorted input
+/// gives sorted output.
+pub fn split_at(notes: &[Note<Row>], row: Row) -> (Vec<Note<Row>>, Vec<Note<Row>>) {
+    let mut first = Vec::new();
+    let mut cut = Vec::new();
+    let mut second = Vec::new();
+    for note in notes {
+        if note.pos >= row {
+            second.push(note.clone());
+            continue;
+        }
+        match note.kind {
+            NoteKind::Hold { end_pos } if end_pos > row => {
+                first.push(Note {
+                    kind: NoteKind::Hold { end_pos: row },
+                    ..note.clone()
+                });
+                cut.push(Note {
+                    pos: row,
+                    ..note.clone()
+                });
+            }
+            NoteKind::Roll { end_pos } if end_pos > row => {
+                first.push(Note {
+                    kind: NoteKind::Roll { end_pos: row },
+                    ..note.clone()
+                });
+                cut.push(Note {
+                    pos: row,
+                    ..note.clone()
+                });
+            }
+            _ => first.push(note.clone()),
+        }
+    }
+    let at_row = second.iter().take_while(|note| note.pos == row).count();
+    let mut heads = cut;
+    heads.extend(second.drain(..at_row));
+    heads.sort_by_key(|note| note.column);
+    heads.extend(second);
+    (first, heads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse_notes;
+
+    #[test]
+    fn test_lengths() {
+        let mut notes = parse_notes("0 2400\n12 0300\n24 3000").unwrap();
+        extend_to_min_length(&mut notes, 36);
+        assert_eq!(notes, parse_notes("0 2400\n36 3300").unwrap());
+        trim_to_max_length(&mut notes, 12);
+        assert_eq!(notes, parse_notes("0 2400\n12 3300").unwrap());
+        trim_to_max_length(&mut notes, 0);
+        assert_eq!(notes, parse_notes("0 1100").unwrap());
+
+        let mut notes = [Note {
+            pos: Row(Row::MAX.0 - 12),
+            column: 0,
+            kind: NoteKind::Hold { end_pos: Row::MAX },
+        }];
+        extend_to_min_length(&mut notes, u64::MAX);
+        assert_eq!(notes[0].kind, NoteKind::Hold { end_pos: Row::MAX });
+        trim_to_max_length(&mut notes, u64::MAX);
+        assert_eq!(notes[0].kind, NoteKind::Hold { end_pos: Row::MAX });
+
+        let mut notes = parse_notes("0 2000\n12 3000\n24 1000").unwrap();
+        extend_to_min_length(&mut notes, 48);
+        let mut clamped = notes.clone();
+        clamp_to_next_note(&mut clamped, 12);
+        assert_eq!(clamped, parse_notes("0 2000\n12 3000\n24 1000").unwrap());
+        clamp_to_next_note(&mut notes, 24);
+        assert_eq!(notes, parse_notes("0 1000\n24 1000").unwrap());
+    }
+
+    #[test]
+    fn test_split() {
+        let notes = parse_notes("0 2040\n48 0200\n96 3000\n144 0330").unwrap();
+        let (first, second) = split_at(&notes, Row(48));
+        assert_eq!(first, parse_notes("0 2040\n48 3030").unwrap());
+        assert_eq!(second, parse_notes("48 2240\n96 3000\n144 0330").unwrap());
+
+        let (first, second) = split_at(&notes, Row(0));
+        assert!(first.is_empty());
+        assert_eq!(second, notes);
+    }
+}
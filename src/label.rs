@@ -0,0 +1,159 @@
+/*!
+Treating [label](crate::TempoEventKind::Label) messages as text
+
+Label messages are stored as raw bytes, exactly as they appear in the clipboard data. ArrowVortex
+itself works with UTF-8, but labels that were imported from older simfiles may contain text in
+legacy 8-bit encodings. This module decodes messages as UTF-8 where possible and falls back to
+Latin-1 (ISO 8859-1, every byte is one character) otherwise. Because the fallback maps every byte
+to exactly one character, no label is ever lost: [`decode_label`] reports which encoding it used,
+and [`encode_label`] with that encoding reproduces the original bytes.
+
+```rust
+use arrowvortex_clipboard::{label::{self, LabelEncoding}, TempoEventKind};
+
+let kind = TempoEventKind::label("Chorus ♪");
+assert_eq!(kind.label_text().as_deref(), Some("Chorus ♪"));
+
+// "Café" in Latin-1, as written by an old simfile editor
+let legacy = TempoEventKind::Label { message: b"Caf\xE9".to_vec() };
+let (text, encoding) = label::decode_label(b"Caf\xE9");
+assert_eq!((&*text, encoding), ("Café", LabelEncoding::Latin1));
+assert!(legacy.label_str().unwrap().is_err());
+
+// Round trip without loss
+assert_eq!(label::encode_label(&text, encoding)?, b"Caf\xE9");
+# Ok::<(), label::LabelError>(())
+```
+*/
+
+use crate::TempoEventKind;
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+/// Text encoding of a label message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LabelEncoding {
+    /// UTF-8, as used by ArrowVortex
+    Utf8,
+    /// ISO 8859-1, where every byte is the Unicode character with the same number
+    Latin1,
+}
+
+/// Error that occurs while encoding label text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelError {
+    /// The text contains a character that the encoding cannot represent
+    UnencodableCharacter {
+        /// The character that cannot be encoded
+        character: char,
+        /// The encoding that was requested
+        encoding: LabelEncoding,
+    },
+}
+
+impl core::fmt::Display for LabelError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnencodableCharacter {
+                character,
+                encoding,
+            } => write!(
+                f,
+                "character {:?} cannot be encoded in {:?}",
+                character, encoding
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LabelError {}
+
+/// Decodes a label message as UTF-8, or as Latin-1 if it isn't valid UTF-8
+///
+/// Returns the text along with the encoding that was used. The text is borrowed from `message` if
+/// it is valid UTF-8.
+pub fn decode_label(message: &[u8]) -> (Cow<'_, str>, LabelEncoding) {
+    match core::str::from_utf8(message) {
+        Ok(text) => (Cow::Borrowed(text), LabelEncoding::Utf8),
+        Err(_) => (
+            Cow::Owned(message.iter().map(|&byte| byte as char).collect()),
+            LabelEncoding::Latin1,
+        ),
+    }
+}
+
+/// Encodes label text into a message
+///
+/// Encoding as UTF-8 always succeeds. Encoding as Latin-1 fails if the text contains characters
+/// beyond U+00FF.
+pub fn encode_label(text: &str, encoding: LabelEncoding) -> Result<Vec<u8>, LabelError> {
+    match encoding {
+        LabelEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        LabelEncoding::Latin1 => text
+            .chars()
+            .map(|character| {
+                if (character as u32) <= 0xFF {
+                    Ok(character as u8)
+                } else {
+                    Err(LabelError::UnencodableCharacter {
+                        character,
+                        encoding,
+                    })
+                }
+            })
+            .collect(),
+    }
+}
+
+impl TempoEventKind {
+    /// Creates a label with the given text, encoded as UTF-8
+    pub fn label(text: &str) -> Self {
+        Self::Label {
+            message: text.as_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the message of a label as UTF-8 text, or `None` if this is not a label
+    ///
+    /// Unlike [`TempoEventKind::label_text`], this fails with the validation error if the message
+    /// is not valid UTF-8.
+    pub fn label_str(&self) -> Option<Result<&str, core::str::Utf8Error>> {
+        match self {
+            Self::Label { message } => Some(core::str::from_utf8(message)),
+            _ => None,
+        }
+    }
+
+    /// Returns the message of a label as text, or `None` if this is not a label
+    ///
+    /// Messages that aren't valid UTF-8 are decoded as Latin-1, see [`decode_label`].
+    pub fn label_text(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Self::Label { message } => Some(decode_label(message).0),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for message in &[&b"Drop"[..], "ドロップ".as_bytes(), b"\xFF\x00\x80", b""] {
+            let (text, encoding) = decode_label(message);
+            assert_eq!(&encode_label(&text, encoding).unwrap(), message);
+        }
+        assert_eq!(decode_label("ü".as_bytes()).1, LabelEncoding::Utf8);
+        assert_eq!(
+            encode_label("x→y", LabelEncoding::Latin1),
+            Err(LabelError::UnencodableCharacter {
+                character: '→',
+                encoding: LabelEncoding::Latin1,
+            })
+        );
+        assert_eq!(TempoEventKind::Scroll { ratio: 1.0 }.label_text(), None);
+    }
+}
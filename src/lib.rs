@@ -1,4 +1,5 @@
 #![warn(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 /*!
 Small library for encoding and decoding [ArrowVortex](https://arrowvortex.ddrnl.com/) clipboard
@@ -19,22 +20,67 @@ let notes = match arrowvortex_clipboard::decode(data.as_bytes())? {
 };
 println!("{:?}", notes);
 
-// Encode &[Note] into string
-let buffer = arrowvortex_clipboard::encode_row_based_notes(&notes)?;
+// Encode &[Note] into string. Decoded notes are sorted and in range, so this cannot fail
+let buffer = arrowvortex_clipboard::encode_row_based_notes(&notes).unwrap();
 println!("{}", buffer);
 
 // Verify that string stayed identical after roundtrip
 assert_eq!(data, buffer);
-# Ok::<(), Box<dyn std::error::Error>>(())
+# Ok::<(), arrowvortex_clipboard::DecodeError>(())
 ```
+
+To read and write notes by hand, for example in test fixtures, see the [`text`] notation.
+
+# `no_std` support
+
+Without the default `std` feature, this crate is `no_std` and only depends on `core` and `alloc`.
+Everything except the `std::error::Error` implementations of the error types stays available.
 */
 
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+mod math;
+
 mod decode;
 pub use decode::*;
 
 mod encode;
 pub use encode::*;
 
+mod position;
+pub use position::{NotePosition, RawPosition};
+
+mod row;
+pub use row::Row;
+
+pub mod base85;
+pub mod beat;
+pub mod difficulty;
+pub mod footing;
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
+pub mod generate;
+pub mod holds;
+pub mod label;
+pub mod mines;
+pub mod mode;
+pub mod patterns;
+pub mod preview;
+pub mod remap;
+pub mod render;
+pub mod scroll;
+pub mod set_ops;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+pub mod sync;
+pub mod text;
+pub mod timing;
+
+/// Number of rows per beat. ArrowVortex places notes on a grid of 192 rows per 4/4 measure
+pub const ROWS_PER_BEAT: u64 = 48;
+
 /// Note-type specific data
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum NoteKind<P> {
@@ -117,16 +163,18 @@ pub enum TempoEventKind {
         /// Miss multiplier
         miss_multiplier: u32,
     },
-    /// Unknown
+    /// Gradually changes the spacing between all notes on screen, starting at this row. See
+    /// [`scroll`] for how this is displayed
     Speed {
-        /// Unknown
+        /// Spacing multiplier that is reached at the end of the transition
         ratio: f64,
-        /// Unknown
+        /// Length of the transition from the previous multiplier, in beats or in seconds
         delay: f64,
-        /// Unknown
+        /// Whether `delay` is in seconds instead of beats
         delay_is_time: bool,
     },
-    /// Changes scroll speed
+    /// Changes the spacing between the notes in the following rows, until the next scroll change.
+    /// See [`scroll`] for how this is displayed
     Scroll {
         /// Scroll speed multiplier
         ratio: f64,
@@ -136,9 +184,9 @@ pub enum TempoEventKind {
         /// Length in rows
         num_fake_rows: u32,
     },
-    /// Label with arbitrary content
+    /// Label with arbitrary content. See [`label`] for working with the message as text
     Label {
-        /// Message content
+        /// Message content, as raw bytes
         message: Vec<u8>,
     },
 }
@@ -147,7 +195,7 @@ pub enum TempoEventKind {
 #[derive(Debug, Clone, PartialEq)]
 pub struct TempoEvent {
     /// Row position of this tempo event
-    pub row: u32,
+    pub row: Row,
     /// Type and type-specific for this tempo event
     pub kind: TempoEventKind,
 }
@@ -0,0 +1,107 @@
+//! Floating point functions that are only inherent methods with std
+
+/// Provides the float methods of std through libm when std is not available
+///
+/// With std, the inherent methods take precedence, so this trait is only imported without it. The
+/// same happens when a dependency links std anyway, which leaves the trait unused.
+#[cfg(not(feature = "std"))]
+#[allow(dead_code)]
+pub(crate) trait Float: Sized {
+    fn sqrt(self) -> Self;
+    fn ln(self) -> Self;
+    fn exp(self) -> Self;
+    fn cos(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn round(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    // Inherent in core since Rust 1.85, but needed for older compilers
+    fn abs(self) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f64 {
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+
+    fn ln(self) -> Self {
+        libm::log(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::exp(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        libm::atan2(self, other)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        libm::pow(self, n as f64)
+    }
+
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn abs(self) -> Self {
+        libm::fabs(self)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f32 {
+    fn sqrt(self) -> Self {
+        libm::sqrtf(self)
+    }
+
+    fn ln(self) -> Self {
+        libm::logf(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::expf(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cosf(self)
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        libm::atan2f(self, other)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        libm::powf(self, n as f32)
+    }
+
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floorf(self)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceilf(self)
+    }
+
+    fn abs(self) -> Self {
+        libm::fabsf(self)
+    }
+}
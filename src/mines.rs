@@ -0,0 +1,237 @@
+/*!
+Removal and placement of mines
+
+[`strip`] drops every mine, [`remove_colliding`] only those that overlap other notes or sit right
+before them, and [`place`] adds mines following a [`MinePattern`]. All functions return notes sorted
+by position and column, ready for [`encode_row_based_notes`](crate::encode_row_based_notes).
+
+```rust
+use arrowvortex_clipboard::{mines::{self, MinePattern}, text};
+
+// Mines on the free columns half a beat after each jump, except where they would hit a note
+let notes = text::parse_notes("0 1001\n24 0100")?;
+let with_mines = mines::place(&notes, MinePattern::AfterJumps { offset: 24 }, 4);
+assert_eq!(with_mines, text::parse_notes("0 1001\n24 01M0")?);
+assert_eq!(mines::strip(&with_mines), notes);
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::{Note, NoteKind, Row};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Where [`place`] puts mines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MinePattern {
+    /// On every column without a note, `offset` rows after each row with two or more notes
+    AfterJumps {
+        /// Distance from the jump in rows
+        offset: u64,
+    },
+    /// In the column of each hold and roll, `offset` rows after it is released
+    HoldReleases {
+        /// Distance from the end of the hold in rows. Must be more than zero, because a mine on
+        /// the last row of a hold would collide with it
+        offset: u64,
+    },
+}
+
+fn sorted(mut notes: Vec<Note<Row>>) -> Vec<Note<Row>> {
+    notes.sort_by_key(|note| (note.pos, note.column));
+    notes
+}
+
+fn end_pos(note: &Note<Row>) -> Row {
+    match note.kind {
+        NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => end_pos.max(note.pos),
+        _ => note.pos,
+    }
+}
+
+/// Rows covered by the notes other than mines, per column, sorted by start
+struct Spans(BTreeMap<u8, Vec<(Row, Row)>>);
+
+impl Spans {
+    fn new(notes: &[Note<Row>]) -> Self {
+        let mut spans = BTreeMap::<u8, Vec<(Row, Row)>>::new();
+        for note in notes {
+            if note.kind != NoteKind::Mine {
+                spans
+                    .entry(note.column)
+                    .or_default()
+                    .push((note.pos, end_pos(note)));
+            }
+        }
+        for column_spans in spans.values_mut() {
+            column_spans.sort_unstable();
+        }
+        Self(spans)
+    }
+
+    /// Whether a mine at the given spot would be on a note, inside a hold or at most `gap` rows
+    /// before a note
+    fn collides(&self, column: u8, pos: Row, gap: u64) -> bool {
+        let column_spans = match self.0.get(&column) {
+            Some(column_spans) => column_spans,
+            None => return false,
+        };
+        // Holds may overlap in unusual input, so every span that starts early enough is checked
+        let num_started = column_spans
+            .binary_search_by(|&(start, _)| {
+                if start.0 <= pos.0.saturating_add(gap) {
+                    core::cmp::Ordering::Less
+                } else {
+                    core::cmp::Ordering::Greater
+                }
+            })
+            .unwrap_or_else(|index| index);
+        column_spans[..num_started]
+            .iter()
+            .any(|&(_, end)| end >= pos)
+    }
+}
+
+/// Removes every mine
+pub fn strip(notes: &[Note<Row>]) -> Vec<Note<Row>> {
+    sorted(
+        notes
+            .iter()
+            .filter(|note| note.kind != NoteKind::Mine)
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Removes mines that are on the same spot as another note, inside a hold or roll, or at most
+/// `gap` rows before another note in the same column
+///
+/// Use a `gap` of zero to only remove mines that overlap other notes.
+pub fn remove_colliding(notes: &[Note<Row>], gap: u64) -> Vec<Note<Row>> {
+    let spans = Spans::new(notes);
+    sorted(
+        notes
+            .iter()
+            .filter(|note| {
+                note.kind != NoteKind::Mine || !spans.collides(note.column, note.pos, gap)
+            })
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Adds mines following `pattern` to the notes, in columns below `num_columns`
+///
+/// Mines that would land on another note, inside a hold or roll, or on an existing mine are left
+/// out, as are mines beyond [`Row::MAX`].
+pub fn place(notes: &[Note<Row>], pattern: MinePattern, num_columns: u8) -> Vec<Note<Row>> {
+    let mut rows = BTreeMap::<Row, Vec<u8>>::new();
+    for note in notes {
+        if note.kind != NoteKind::Mine {
+            rows.entry(note.pos).or_default().push(note.column);
+        }
+    }
+
+    let mut spots = Vec::new();
+    match pattern {
+        MinePattern::AfterJumps { offset } => {
+            for (&pos, columns) in rows.iter().filter(|(_, columns)| columns.len() >= 2) {
+                let pos = match pos.checked_add(offset) {
+                    Some(pos) => pos,
+                    None => continue,
+                };
+                spots.extend(
+                    (0..num_columns)
+                        .filter(|column| !columns.contains(column))
+                        .map(|column| (pos, column)),
+                );
+            }
+        }
+        MinePattern::HoldReleases { offset } => {
+            for note in notes {
+                if let NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } = note.kind {
+                    match end_pos.checked_add(offset) {
+                        Some(pos) if note.column < num_columns => spots.push((pos, note.column)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    let spans = Spans::new(notes);
+    let mut output = notes.to_vec();
+    output.extend(
+        spots
+            .into_iter()
+            .filter(|&(pos, column)| pos <= Row::MAX && !spans.collides(column, pos, 0))
+            .map(|(pos, column)| Note {
+                pos,
+                column,
+                kind: NoteKind::Mine,
+            }),
+    );
+    let mut output = sorted(output);
+    output.dedup_by(|a, b| a.kind == NoteKind::Mine && b.kind == NoteKind::Mine && a == b);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse_notes;
+
+    #[test]
+    fn test_remove() {
+        let mut notes = parse_notes("0 2000\n24 3M00\n36 M100\n48 0M01").unwrap();
+        // A mine inside the hold, which text notation cannot express
+        notes.insert(
+            1,
+            Note {
+                pos: Row(12),
+                column: 0,
+                kind: NoteKind::Mine,
+            },
+        );
+        assert_eq!(
+            remove_colliding(&notes, 0),
+            parse_notes("0 2000\n24 3M00\n36 M100\n48 0M01").unwrap(),
+        );
+        assert_eq!(
+            remove_colliding(&notes, 12),
+            parse_notes("0 2000\n24 3000\n36 M100\n48 0M01").unwrap(),
+        );
+        assert_eq!(
+            strip(&notes),
+            parse_notes("0 2000\n24 3000\n36 0100\n48 0001").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_place() {
+        let notes = parse_notes("0 2000\n6 0011\n24 3000\n48 1100").unwrap();
+        let releases = place(&notes, MinePattern::HoldReleases { offset: 12 }, 4);
+        assert_eq!(
+            releases,
+            parse_notes("0 2000\n6 0011\n24 3000\n36 M000\n48 1100").unwrap()
+        );
+
+        // Column 0 after the jump at row 6 is inside the hold
+        let jumps = place(&notes, MinePattern::AfterJumps { offset: 12 }, 4);
+        assert_eq!(
+            jumps,
+            parse_notes("0 2000\n6 0011\n18 0M00\n24 3000\n48 1100\n60 00MM").unwrap()
+        );
+        assert_eq!(
+            place(&jumps, MinePattern::AfterJumps { offset: 12 }, 4),
+            jumps
+        );
+
+        // Mines past the last row are left out instead of overflowing
+        let offset = u64::MAX;
+        assert_eq!(
+            place(&notes, MinePattern::HoldReleases { offset }, 4),
+            notes
+        );
+        assert_eq!(place(&notes, MinePattern::AfterJumps { offset }, 4), notes);
+    }
+}
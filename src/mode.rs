@@ -0,0 +1,417 @@
+/*!
+Game modes and their lane layouts
+
+ArrowVortex stores notes with a bare column index and leaves it to the chart's game mode to say
+what each column means. [`GameMode`] describes the modes known from StepMania: how many columns
+they have, which [`Lane`] every column is and how the columns are split across pads.
+
+[`Selection`] ties notes to a game mode and rejects notes outside of it when encoding or decoding.
+
+```rust
+use arrowvortex_clipboard::mode::{GameMode, Lane, Selection};
+use arrowvortex_clipboard::{Note, NoteKind, Row};
+
+let mode = GameMode::DanceDouble;
+assert_eq!(mode.num_columns(), 8);
+assert_eq!(mode.lanes()[4], Lane::Left);
+assert_eq!(mode.pad_of(5), Some(1));
+
+let selection = Selection {
+    mode: GameMode::DanceSingle,
+    notes: vec![Note { pos: Row(0), column: 5, kind: NoteKind::Tap }],
+};
+assert!(selection.encode().is_err());
+```
+*/
+
+use crate::{DecodeError, EncodeError, Note, NotePosition};
+use alloc::{string::String, vec::Vec};
+
+/// Direction or role of a single column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lane {
+    /// Arrow pointing left
+    Left,
+    /// Arrow pointing down
+    Down,
+    /// Arrow pointing up
+    Up,
+    /// Arrow pointing right
+    Right,
+    /// Arrow pointing up and left
+    UpLeft,
+    /// Arrow pointing up and right
+    UpRight,
+    /// Arrow pointing down and left
+    DownLeft,
+    /// Arrow pointing down and right
+    DownRight,
+    /// Center panel
+    Center,
+    /// Numbered key of a keyboard mode, starting at 1
+    Key(u8),
+}
+
+impl Lane {
+    /// Direction of the arrow in eighth turns clockwise from upwards, or `None` for lanes without
+    /// a direction
+    pub(crate) fn eighth_turns(self) -> Option<u8> {
+        Some(match self {
+            Self::Up => 0,
+            Self::UpRight => 1,
+            Self::Right => 2,
+            Self::DownRight => 3,
+            Self::Down => 4,
+            Self::DownLeft => 5,
+            Self::Left => 6,
+            Self::UpLeft => 7,
+            Self::Center | Self::Key(_) => return None,
+        })
+    }
+}
+
+impl core::fmt::Display for Lane {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Left => f.write_str("Left"),
+            Self::Down => f.write_str("Down"),
+            Self::Up => f.write_str("Up"),
+            Self::Right => f.write_str("Right"),
+            Self::UpLeft => f.write_str("UpLeft"),
+            Self::UpRight => f.write_str("UpRight"),
+            Self::DownLeft => f.write_str("DownLeft"),
+            Self::DownRight => f.write_str("DownRight"),
+            Self::Center => f.write_str("Center"),
+            Self::Key(key) => write!(f, "Key{}", key),
+        }
+    }
+}
+
+const DANCE: [Lane; 4] = [Lane::Left, Lane::Down, Lane::Up, Lane::Right];
+const DANCE_DOUBLE: [Lane; 8] = [
+    Lane::Left,
+    Lane::Down,
+    Lane::Up,
+    Lane::Right,
+    Lane::Left,
+    Lane::Down,
+    Lane::Up,
+    Lane::Right,
+];
+const DANCE_SOLO: [Lane; 6] = [
+    Lane::Left,
+    Lane::UpLeft,
+    Lane::Down,
+    Lane::Up,
+    Lane::UpRight,
+    Lane::Right,
+];
+const DANCE_THREEPANEL: [Lane; 3] = [Lane::UpLeft, Lane::Down, Lane::UpRight];
+const PUMP: [Lane; 5] = [
+    Lane::DownLeft,
+    Lane::UpLeft,
+    Lane::Center,
+    Lane::UpRight,
+    Lane::DownRight,
+];
+const PUMP_HALFDOUBLE: [Lane; 6] = [
+    Lane::Center,
+    Lane::UpRight,
+    Lane::DownRight,
+    Lane::DownLeft,
+    Lane::UpLeft,
+    Lane::Center,
+];
+const PUMP_DOUBLE: [Lane; 10] = [
+    Lane::DownLeft,
+    Lane::UpLeft,
+    Lane::Center,
+    Lane::UpRight,
+    Lane::DownRight,
+    Lane::DownLeft,
+    Lane::UpLeft,
+    Lane::Center,
+    Lane::UpRight,
+    Lane::DownRight,
+];
+const TECHNO_EIGHT: [Lane; 8] = [
+    Lane::DownLeft,
+    Lane::Left,
+    Lane::UpLeft,
+    Lane::Down,
+    Lane::Up,
+    Lane::UpRight,
+    Lane::Right,
+    Lane::DownRight,
+];
+const KB7: [Lane; 7] = [
+    Lane::Key(1),
+    Lane::Key(2),
+    Lane::Key(3),
+    Lane::Key(4),
+    Lane::Key(5),
+    Lane::Key(6),
+    Lane::Key(7),
+];
+
+/// Game mode of a chart, called steps type in StepMania
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// One pad with four arrows
+    DanceSingle,
+    /// Two dance-single pads played by one player
+    DanceDouble,
+    /// Two dance-single pads played by two players
+    DanceCouple,
+    /// One pad with six arrows, including the upper diagonals
+    DanceSolo,
+    /// One pad with the upper diagonals and the down arrow
+    DanceThreepanel,
+    /// One pad with four diagonal arrows and a center panel
+    PumpSingle,
+    /// The six inner panels of two pump-single pads
+    PumpHalfdouble,
+    /// Two pump-single pads played by one player
+    PumpDouble,
+    /// Two pump-single pads played by two players
+    PumpCouple,
+    /// Techno with the four dance-single arrows
+    TechnoSingle4,
+    /// Techno with the five pump-single panels
+    TechnoSingle5,
+    /// Techno with all eight arrows around the center
+    TechnoSingle8,
+    /// Two techno-single4 pads
+    TechnoDouble4,
+    /// Two techno-single5 pads
+    TechnoDouble5,
+    /// Seven keys on a keyboard
+    Kb7Single,
+}
+
+impl GameMode {
+    /// All game modes
+    pub const ALL: [Self; 15] = [
+        Self::DanceSingle,
+        Self::DanceDouble,
+        Self::DanceCouple,
+        Self::DanceSolo,
+        Self::DanceThreepanel,
+        Self::PumpSingle,
+        Self::PumpHalfdouble,
+        Self::PumpDouble,
+        Self::PumpCouple,
+        Self::TechnoSingle4,
+        Self::TechnoSingle5,
+        Self::TechnoSingle8,
+        Self::TechnoDouble4,
+        Self::TechnoDouble5,
+        Self::Kb7Single,
+    ];
+
+    /// Name of this game mode in StepMania's `#STEPSTYPE`, like `dance-single`
+    pub fn steps_type(self) -> &'static str {
+        match self {
+            Self::DanceSingle => "dance-single",
+            Self::DanceDouble => "dance-double",
+            Self::DanceCouple => "dance-couple",
+            Self::DanceSolo => "dance-solo",
+            Self::DanceThreepanel => "dance-threepanel",
+            Self::PumpSingle => "pump-single",
+            Self::PumpHalfdouble => "pump-halfdouble",
+            Self::PumpDouble => "pump-double",
+            Self::PumpCouple => "pump-couple",
+            Self::TechnoSingle4 => "techno-single4",
+            Self::TechnoSingle5 => "techno-single5",
+            Self::TechnoSingle8 => "techno-single8",
+            Self::TechnoDouble4 => "techno-double4",
+            Self::TechnoDouble5 => "techno-double5",
+            Self::Kb7Single => "kb7-single",
+        }
+    }
+
+    /// Looks up a game mode by its [steps type](Self::steps_type), ignoring ASCII case
+    pub fn from_steps_type(steps_type: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.steps_type().eq_ignore_ascii_case(steps_type))
+    }
+
+    /// Lane of every column, from left to right
+    pub fn lanes(self) -> &'static [Lane] {
+        match self {
+            Self::DanceSingle | Self::TechnoSingle4 => &DANCE,
+            Self::DanceDouble | Self::DanceCouple | Self::TechnoDouble4 => &DANCE_DOUBLE,
+            Self::DanceSolo => &DANCE_SOLO,
+            Self::DanceThreepanel => &DANCE_THREEPANEL,
+            Self::PumpSingle | Self::TechnoSingle5 => &PUMP,
+            Self::PumpHalfdouble => &PUMP_HALFDOUBLE,
+            Self::PumpDouble | Self::PumpCouple | Self::TechnoDouble5 => &PUMP_DOUBLE,
+            Self::TechnoSingle8 => &TECHNO_EIGHT,
+            Self::Kb7Single => &KB7,
+        }
+    }
+
+    /// Number of columns
+    pub fn num_columns(self) -> u8 {
+        self.lanes().len() as u8
+    }
+
+    /// Lane of the given column, or `None` if the column is not part of this game mode
+    pub fn lane(self, column: u8) -> Option<Lane> {
+        self.lanes().get(column as usize).copied()
+    }
+
+    /// Number of pads the columns are split across. Every pad has the same number of columns
+    pub fn num_pads(self) -> u8 {
+        match self {
+            Self::DanceDouble
+            | Self::DanceCouple
+            | Self::PumpHalfdouble
+            | Self::PumpDouble
+            | Self::PumpCouple
+            | Self::TechnoDouble4
+            | Self::TechnoDouble5 => 2,
+            _ => 1,
+        }
+    }
+
+    /// Columns of the given pad, counting pads from the left
+    ///
+    /// The range is empty if the game mode doesn't have that many pads.
+    pub fn pad_columns(self, pad: u8) -> core::ops::Range<u8> {
+        let num_columns = self.num_columns();
+        if pad >= self.num_pads() {
+            return num_columns..num_columns;
+        }
+        let columns_per_pad = num_columns / self.num_pads();
+        // Both products are at most the number of columns, so they can't overflow
+        pad * columns_per_pad..(pad + 1) * columns_per_pad
+    }
+
+    /// Pad that the given column is on, or `None` if the column is not part of this game mode
+    pub fn pad_of(self, column: u8) -> Option<u8> {
+        if column < self.num_columns() {
+            Some(column / (self.num_columns() / self.num_pads()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the first note whose column is not part of this game mode
+    fn find_invalid_column<P>(self, notes: &[Note<P>]) -> Option<u8> {
+        notes
+            .iter()
+            .map(|note| note.column)
+            .find(|&column| column >= self.num_columns())
+    }
+}
+
+impl core::fmt::Display for GameMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.steps_type())
+    }
+}
+
+/// Notes of a specific game mode
+///
+/// Unlike the free encoding functions, which accept any column below 128, [`Selection::encode`]
+/// and [`Selection::decode`] reject notes in columns that the game mode doesn't have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection<P> {
+    /// Game mode that the notes are played in
+    pub mode: GameMode,
+    /// The notes, sorted by position and column
+    pub notes: Vec<Note<P>>,
+}
+
+impl<P: NotePosition> Selection<P> {
+    /// Encodes the notes like [`encode_notes`](crate::encode_notes), after checking that every
+    /// column is part of the game mode
+    pub fn encode(&self) -> Result<String, EncodeError> {
+        if let Some(column) = self.mode.find_invalid_column(&self.notes) {
+            return Err(EncodeError::ColumnOutOfRange {
+                column,
+                num_columns: self.mode.num_columns(),
+            });
+        }
+        crate::encode_notes(&self.notes)
+    }
+
+    /// Decodes notes like [`decode_notes`](crate::decode_notes) and checks that every column is
+    /// part of the given game mode
+    pub fn decode(mode: GameMode, data: &[u8]) -> Result<Self, DecodeError> {
+        let notes = crate::decode_notes(data)?;
+        if let Some(column) = mode.find_invalid_column(&notes) {
+            return Err(DecodeError::ColumnOutOfRange {
+                column,
+                num_columns: mode.num_columns(),
+            });
+        }
+        Ok(Self { mode, notes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NoteKind, Row};
+    use alloc::vec;
+
+    #[test]
+    fn test_layouts() {
+        for &mode in &GameMode::ALL {
+            assert_eq!(GameMode::from_steps_type(mode.steps_type()), Some(mode));
+            let num_pads = mode.num_pads();
+            let pad_columns = (0..num_pads)
+                .map(|pad| mode.pad_columns(pad).len())
+                .sum::<usize>();
+            assert_eq!(pad_columns, mode.num_columns() as usize);
+            assert_eq!(mode.pad_of(mode.num_columns() - 1), Some(num_pads - 1));
+            assert_eq!(mode.pad_of(mode.num_columns()), None);
+        }
+        assert_eq!(GameMode::PumpDouble.pad_columns(1), 5..10);
+        assert!(GameMode::PumpDouble.pad_columns(2).is_empty());
+        assert!(GameMode::Kb7Single.pad_columns(u8::MAX).is_empty());
+        assert_eq!(GameMode::Kb7Single.lane(6), Some(Lane::Key(7)));
+        assert_eq!(GameMode::Kb7Single.lane(6).unwrap().to_string(), "Key7");
+    }
+
+    #[test]
+    fn test_selection() {
+        let tap = |column| Note {
+            pos: Row(0),
+            column,
+            kind: NoteKind::Tap,
+        };
+        let double = Selection {
+            mode: GameMode::DanceDouble,
+            notes: vec![tap(0), tap(7)],
+        };
+        let data = double.encode().unwrap();
+        assert_eq!(
+            Selection::decode(GameMode::DanceDouble, data.as_bytes()).unwrap(),
+            double
+        );
+        assert!(matches!(
+            Selection::<Row>::decode(GameMode::DanceSingle, data.as_bytes()),
+            Err(DecodeError::ColumnOutOfRange {
+                column: 7,
+                num_columns: 4
+            })
+        ));
+
+        let single = Selection {
+            mode: GameMode::DanceSingle,
+            notes: vec![tap(0), tap(7)],
+        };
+        assert!(matches!(
+            single.encode(),
+            Err(EncodeError::ColumnOutOfRange {
+                column: 7,
+                num_columns: 4
+            })
+        ));
+    }
+}
@@ -0,0 +1,393 @@
+/*!
+Recognition of common step patterns in a note selection
+
+Patterns come in two flavors:
+
+- Flow patterns describe what a longer, uninterrupted section is made of: [`Pattern::Stream`],
+  [`Pattern::Jumpstream`], [`Pattern::Handstream`] and [`Pattern::Chordjacks`]. They never overlap
+  each other.
+- Technical patterns are short shapes that may appear inside a flow pattern, such as trills,
+  staircases or candles.
+
+Only notes that are stepped on are considered: taps, hold and roll heads and lifts. Mines and fakes
+are ignored. Candles, crossovers and footswitches assume a four-panel dance layout (dance-single,
+or each pad of dance-double) and alternating feet; [`crate::footing`] analyzes footing properly.
+
+```rust
+use arrowvortex_clipboard::patterns::{self, Pattern, PatternSegment};
+use arrowvortex_clipboard::{text, Row};
+
+let notes = text::parse_notes("
+    0 1000
+    12 0100
+    24 0010
+    36 0001
+    48 1000
+    60 0100
+    72 0010
+    84 0001
+")?;
+
+let segments = patterns::detect(&notes, &patterns::PatternOptions::default());
+assert_eq!(segments, [
+    PatternSegment { start_row: Row(0), end_row: Row(84), pattern: Pattern::Stream },
+    PatternSegment { start_row: Row(0), end_row: Row(84), pattern: Pattern::Roll },
+]);
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::{Note, NoteKind, Row, ROWS_PER_BEAT};
+use alloc::{vec, vec::Vec};
+
+/// Pattern label of a [`PatternSegment`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pattern {
+    /// Single notes in quick succession, without jacks
+    Stream,
+    /// Stream with jumps (two-note chords) mixed in
+    Jumpstream,
+    /// Stream with hands (chords of three or more notes) mixed in
+    Handstream,
+    /// Consecutive chords that share columns
+    Chordjacks,
+    /// Three or more consecutive single notes on the same column
+    Jacks,
+    /// Two consecutive single notes on the same column
+    Minijack,
+    /// Two consecutive single notes on the same up or down panel, in the middle of a flow, which
+    /// are usually hit with alternating feet
+    Footswitch,
+    /// Single notes alternating between two columns
+    Trill,
+    /// Single notes cycling through three or more columns in the same direction repeatedly
+    Roll,
+    /// Single notes sweeping across at least four adjacent columns in one direction
+    Staircase,
+    /// One foot moving from the up to the down panel or vice versa, via a left or right note
+    Candle,
+    /// One foot moving from the left to the right panel or vice versa, crossing the other foot
+    Crossover,
+}
+
+/// Section of a note selection that was recognized as a [`Pattern`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PatternSegment {
+    /// Row of the first note in this segment
+    pub start_row: Row,
+    /// Row of the last note in this segment
+    pub end_row: Row,
+    /// What this segment contains
+    pub pattern: Pattern,
+}
+
+/// Settings for [`detect`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternOptions {
+    /// Number of columns in the chart. Layout specific patterns such as candles are only detected
+    /// for 4 (dance-single) and 8 (dance-double)
+    pub num_columns: u8,
+    /// Notes that are further apart than this number of rows interrupt a flow pattern
+    pub max_gap: u64,
+    /// Minimum number of rows a flow pattern needs to span
+    pub min_flow_rows: usize,
+}
+
+impl Default for PatternOptions {
+    fn default() -> Self {
+        Self {
+            num_columns: 4,
+            max_gap: ROWS_PER_BEAT / 4,
+            min_flow_rows: 8,
+        }
+    }
+}
+
+struct NoteRow {
+    pos: u64,
+    columns: Vec<u8>,
+}
+
+impl NoteRow {
+    fn shares_column_with(&self, other: &NoteRow) -> bool {
+        self.columns
+            .iter()
+            .any(|column| other.columns.contains(column))
+    }
+}
+
+fn collect_rows(notes: &[Note<Row>]) -> Vec<NoteRow> {
+    let mut rows = alloc::collections::BTreeMap::<u64, Vec<u8>>::new();
+    for note in notes {
+        match note.kind {
+            NoteKind::Mine | NoteKind::Fake => {}
+            _ => rows.entry(note.pos.0).or_default().push(note.column),
+        }
+    }
+    rows.into_iter()
+        .map(|(pos, mut columns)| {
+            columns.sort_unstable();
+            columns.dedup();
+            NoteRow { pos, columns }
+        })
+        .collect()
+}
+
+/// Splits `items` into maximal runs of consecutive items for which `key` returns the same value
+fn runs<T, K: PartialEq>(
+    items: &[T],
+    key: impl Fn(usize) -> K,
+) -> Vec<(K, core::ops::Range<usize>)> {
+    let mut runs: Vec<(K, core::ops::Range<usize>)> = Vec::new();
+    for i in 0..items.len() {
+        let k = key(i);
+        match runs.last_mut() {
+            Some((last_key, range)) if *last_key == k => range.end = i + 1,
+            _ => runs.push((k, i..i + 1)),
+        }
+    }
+    runs
+}
+
+fn detect_flow(rows: &[NoteRow], options: &PatternOptions, segments: &mut Vec<PatternSegment>) {
+    let is_chord = |i: usize| rows[i].columns.len() >= 2;
+    let shares_with_prev = |i: usize| i > 0 && rows[i].shares_column_with(&rows[i - 1]);
+
+    let mut is_chordjack = vec![false; rows.len()];
+    for i in 1..rows.len() {
+        if is_chord(i) && is_chord(i - 1) && shares_with_prev(i) {
+            is_chordjack[i - 1] = true;
+            is_chordjack[i] = true;
+        }
+    }
+
+    for (chordjack, range) in runs(rows, |i| is_chordjack[i]) {
+        if range.len() < options.min_flow_rows {
+            continue;
+        }
+        let pattern = if chordjack {
+            Pattern::Chordjacks
+        } else {
+            // Sections made up mostly of jacks are not a stream
+            let num_jacks = range.clone().filter(|&i| shares_with_prev(i)).count();
+            if num_jacks * 2 > range.len() {
+                continue;
+            }
+            match range.clone().map(|i| rows[i].columns.len()).max() {
+                Some(1) => Pattern::Stream,
+                Some(2) => Pattern::Jumpstream,
+                _ => Pattern::Handstream,
+            }
+        };
+        segments.push(PatternSegment {
+            start_row: Row(rows[range.start].pos),
+            end_row: Row(rows[range.end - 1].pos),
+            pattern,
+        });
+    }
+}
+
+/// Returns whether the columns form a single cyclic sweep in one direction, like `1 2 3 0`
+fn is_cyclic_sweep(columns: &[u8]) -> bool {
+    let n = columns.len();
+    let num_descents = (0..n)
+        .filter(|&i| columns[(i + 1) % n] < columns[i])
+        .count();
+    let num_ascents = (0..n)
+        .filter(|&i| columns[(i + 1) % n] > columns[i])
+        .count();
+    num_ascents + num_descents == n && (num_descents == 1 || num_ascents == 1)
+}
+
+/// Detects technical patterns in a sequence of consecutive single notes
+fn detect_technical(
+    pos: &[u64],
+    columns: &[u8],
+    options: &PatternOptions,
+    segments: &mut Vec<PatternSegment>,
+) {
+    let n = columns.len();
+    let is_dance = options.num_columns == 4 || options.num_columns == 8;
+    let mut push = |start: usize, end: usize, pattern: Pattern| {
+        segments.push(PatternSegment {
+            start_row: Row(pos[start]),
+            end_row: Row(pos[end]),
+            pattern,
+        })
+    };
+
+    // Jacks, minijacks and footswitches
+    for (column, range) in runs(columns, |i| columns[i]) {
+        let (start, end) = (range.start, range.end - 1);
+        match range.len() {
+            1 => {}
+            2 => {
+                let is_middle_panel = column % 4 == 1 || column % 4 == 2;
+                let is_embedded = start > 0 && end + 1 < n;
+                if is_dance && is_middle_panel && is_embedded {
+                    push(start, end, Pattern::Footswitch);
+                } else {
+                    push(start, end, Pattern::Minijack);
+                }
+            }
+            _ => push(start, end, Pattern::Jacks),
+        }
+    }
+
+    // Trills
+    let is_trilling =
+        |i: usize| i >= 2 && columns[i] == columns[i - 2] && columns[i] != columns[i - 1];
+    for (trilling, range) in runs(columns, is_trilling) {
+        // The two notes before the first matching note belong to the trill too
+        if trilling && range.len() + 2 >= 4 {
+            push(range.start - 2, range.end - 1, Pattern::Trill);
+        }
+    }
+
+    // Rolls, preferring the longest period
+    let mut roll_ranges = Vec::new();
+    for period in (3..=options.num_columns.max(3) as usize).rev() {
+        let is_rolling = |i: usize| {
+            i >= period
+                && columns[i] == columns[i - period]
+                && is_cyclic_sweep(&columns[i - period..i])
+        };
+        for (rolling, range) in runs(columns, is_rolling) {
+            let range = range.start.saturating_sub(period)..range.end;
+            let is_covered = roll_ranges
+                .iter()
+                .any(|r: &core::ops::Range<usize>| r.start <= range.start && range.end <= r.end);
+            if rolling && range.len() >= 2 * period && !is_covered {
+                push(range.start, range.end - 1, Pattern::Roll);
+                roll_ranges.push(range);
+            }
+        }
+    }
+
+    // Staircases, unless they are part of a roll
+    let step = |i: usize| i > 0 && (columns[i] as i16 - columns[i - 1] as i16).abs() == 1;
+    let direction = |i: usize| {
+        if step(i) {
+            columns[i] as i16 - columns[i - 1] as i16
+        } else {
+            0
+        }
+    };
+    for (direction, range) in runs(columns, direction) {
+        if direction == 0 {
+            continue;
+        }
+        let range = range.start - 1..range.end;
+        let is_covered = roll_ranges
+            .iter()
+            .any(|r| r.start <= range.start && range.end <= r.end);
+        if range.len() >= 4 && !is_covered {
+            push(range.start, range.end - 1, Pattern::Staircase);
+        }
+    }
+
+    // Candles and crossovers
+    if is_dance {
+        for i in 2..n {
+            let (a, b, c) = (columns[i - 2], columns[i - 1], columns[i]);
+            if a / 4 != b / 4 || b / 4 != c / 4 {
+                continue;
+            }
+            let (a, b, c) = (a % 4, b % 4, c % 4);
+            let is_side = |column: u8| column == 0 || column == 3;
+            if !is_side(a) && !is_side(c) && a != c && is_side(b) {
+                push(i - 2, i, Pattern::Candle);
+            } else if is_side(a) && is_side(c) && a != c && !is_side(b) {
+                push(i - 2, i, Pattern::Crossover);
+            }
+        }
+    }
+}
+
+/// Labels the sections of `notes` with the patterns they contain
+///
+/// The returned segments are sorted by start row. Flow patterns are listed before technical
+/// patterns that start on the same row.
+pub fn detect(notes: &[Note<Row>], options: &PatternOptions) -> Vec<PatternSegment> {
+    let rows = collect_rows(notes);
+    let mut segments = Vec::new();
+
+    let is_connected = |i: usize| i > 0 && rows[i].pos - rows[i - 1].pos <= options.max_gap;
+    let mut run_start = 0;
+    for i in 1..=rows.len() {
+        if i < rows.len() && is_connected(i) {
+            continue;
+        }
+        let run = &rows[run_start..i];
+        detect_flow(run, options, &mut segments);
+
+        // Technical patterns are made up of single notes; chords interrupt them
+        for (is_single, range) in runs(run, |j| run[j].columns.len() == 1) {
+            if is_single {
+                let pos = run[range.clone()]
+                    .iter()
+                    .map(|row| row.pos)
+                    .collect::<Vec<_>>();
+                let columns = run[range]
+                    .iter()
+                    .map(|row| row.columns[0])
+                    .collect::<Vec<_>>();
+                detect_technical(&pos, &columns, options, &mut segments);
+            }
+        }
+        run_start = i;
+    }
+
+    segments.sort_by_key(|segment| (segment.start_row, segment.pattern));
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_text(text: &str) -> Vec<(u64, u64, Pattern)> {
+        let notes = crate::text::parse_notes(text).unwrap();
+        detect(&notes, &PatternOptions::default())
+            .into_iter()
+            .map(|segment| (segment.start_row.0, segment.end_row.0, segment.pattern))
+            .collect()
+    }
+
+    #[test]
+    fn test_flow() {
+        let jumpstream = "0 1001\n12 0100\n24 0010\n36 1100\n48 0001\n60 0010\n72 1001\n84 0100";
+        assert_eq!(detect_text(jumpstream), [(0, 84, Pattern::Jumpstream)]);
+
+        let chordjacks = "0 1100\n12 1010\n24 0110\n36 0101\n48 1001\n60 1100\n72 0110\n84 1010";
+        assert_eq!(detect_text(chordjacks), [(0, 84, Pattern::Chordjacks)]);
+    }
+
+    #[test]
+    fn test_technical() {
+        assert_eq!(
+            detect_text("0 1000\n12 0100\n24 1000\n36 0100\n48 0010"),
+            [(0, 36, Pattern::Trill)]
+        );
+        assert_eq!(
+            detect_text("0 0001\n12 0010\n24 0100\n36 1000"),
+            [(0, 36, Pattern::Staircase)]
+        );
+        assert_eq!(
+            detect_text("0 0010\n12 1000\n24 0100\n36 0001\n48 0001"),
+            [
+                (0, 24, Pattern::Candle),
+                (12, 36, Pattern::Crossover),
+                (36, 48, Pattern::Minijack),
+            ]
+        );
+        assert_eq!(
+            detect_text("0 1000\n12 0010\n24 0010\n36 0001"),
+            [(12, 24, Pattern::Footswitch)]
+        );
+        assert_eq!(
+            detect_text("0 0010\n12 0010\n24 0010\n36 0001"),
+            [(0, 24, Pattern::Jacks)]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Custom note position types for encoding and decoding
+
+pub(crate) mod sealed {
+    /// Implementation details of [`RawPosition`](super::RawPosition)
+    pub trait Sealed: Copy + PartialOrd + 'static {
+        /// Whether positions of this type are stored in seconds
+        const TIME_BASED: bool;
+
+        fn to_bits(self) -> u64;
+
+        fn from_bits(bits: u64) -> Self;
+    }
+
+    impl Sealed for u64 {
+        const TIME_BASED: bool = false;
+
+        fn to_bits(self) -> u64 {
+            self
+        }
+
+        fn from_bits(bits: u64) -> Self {
+            bits
+        }
+    }
+
+    impl Sealed for f64 {
+        const TIME_BASED: bool = true;
+
+        fn to_bits(self) -> u64 {
+            self.to_bits()
+        }
+
+        fn from_bits(bits: u64) -> Self {
+            f64::from_bits(bits)
+        }
+    }
+}
+
+/// Note position as stored in clipboard data: rows as `u64` or seconds as `f64`
+///
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait RawPosition: sealed::Sealed {}
+
+impl RawPosition for u64 {}
+
+impl RawPosition for f64 {}
+
+/// Type that can be used as position of a [`Note`](crate::Note) when encoding and decoding
+///
+/// Clipboard data stores note positions either as rows or as seconds. Implementors of this trait
+/// stand in for those by converting from and to one of the two raw representations. Used by
+/// [`encode_notes`](crate::encode_notes) and [`decode_notes`](crate::decode_notes), which convert
+/// note by note, so there is no intermediate `Vec<Note<Row>>` or `Vec<Note<f64>>`.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{Note, NoteKind, NotePosition};
+/// use std::convert::TryFrom;
+///
+/// /// Position in beats, as used by a hypothetical editor
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// struct Beat(u32);
+///
+/// impl NotePosition for Beat {
+///     type Raw = u64;
+///
+///     fn to_raw(&self) -> u64 {
+///         self.0 as u64 * arrowvortex_clipboard::ROWS_PER_BEAT
+///     }
+///
+///     fn from_raw(row: u64) -> Option<Self> {
+///         if row % arrowvortex_clipboard::ROWS_PER_BEAT != 0 {
+///             return None;
+///         }
+///         u32::try_from(row / arrowvortex_clipboard::ROWS_PER_BEAT).ok().map(Beat)
+///     }
+/// }
+///
+/// let notes = &[
+///     Note { pos: Beat(0), column: 0, kind: NoteKind::Tap },
+///     Note { pos: Beat(1), column: 1, kind: NoteKind::Tap },
+/// ];
+/// let encoded = arrowvortex_clipboard::encode_notes(notes).unwrap();
+/// assert_eq!(encoded, r#"ArrowVortex:notes:!!3-#!AF"#);
+/// assert_eq!(arrowvortex_clipboard::decode_notes::<Beat>(encoded.as_bytes())?, notes);
+/// # Ok::<(), arrowvortex_clipboard::DecodeError>(())
+/// ```
+pub trait NotePosition: Sized {
+    /// Representation in clipboard data, `u64` for rows or `f64` for seconds
+    type Raw: RawPosition;
+
+    /// Converts this position into its raw representation
+    fn to_raw(&self) -> Self::Raw;
+
+    /// Converts a decoded raw position into this type, or returns `None` if it can't be
+    /// represented. Decoding then fails with [`DecodeError::InvalidPosition`](crate::DecodeError)
+    fn from_raw(raw: Self::Raw) -> Option<Self>;
+}
+
+impl NotePosition for u64 {
+    type Raw = u64;
+
+    fn to_raw(&self) -> u64 {
+        *self
+    }
+
+    fn from_raw(raw: u64) -> Option<Self> {
+        Some(raw)
+    }
+}
+
+impl NotePosition for crate::Row {
+    type Raw = u64;
+
+    fn to_raw(&self) -> u64 {
+        self.0
+    }
+
+    fn from_raw(raw: u64) -> Option<Self> {
+        Some(Self(raw))
+    }
+}
+
+impl NotePosition for f64 {
+    type Raw = f64;
+
+    fn to_raw(&self) -> f64 {
+        *self
+    }
+
+    fn from_raw(raw: f64) -> Option<Self> {
+        Some(raw)
+    }
+}
+
+/// Seconds since the start of the chart. Notes before the start can't be decoded into this
+impl NotePosition for core::time::Duration {
+    type Raw = f64;
+
+    fn to_raw(&self) -> f64 {
+        self.as_secs_f64()
+    }
+
+    fn from_raw(raw: f64) -> Option<Self> {
+        if raw >= 0.0 && raw < u64::MAX as f64 {
+            Some(Self::from_secs_f64(raw))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Note, NoteKind};
+    use core::time::Duration;
+
+    #[test]
+    fn test_duration() {
+        let notes = [
+            Note {
+                pos: Duration::from_millis(250),
+                column: 1,
+                kind: NoteKind::Hold {
+                    end_pos: Duration::from_secs(2),
+                },
+            },
+            Note {
+                pos: Duration::from_millis(500),
+                column: 0,
+                kind: NoteKind::Tap,
+            },
+        ];
+        let encoded = crate::encode_notes(&notes).unwrap();
+        assert_eq!(
+            crate::decode_notes::<Duration>(encoded.as_bytes()).unwrap(),
+            notes
+        );
+
+        // Wrong kind of position
+        assert!(matches!(
+            crate::decode_notes::<u64>(encoded.as_bytes()),
+            Err(crate::DecodeError::UnexpectedDataType)
+        ));
+
+        let before_start = crate::encode_time_based_notes(&[Note {
+            pos: -0.5,
+            column: 0,
+            kind: NoteKind::Tap,
+        }])
+        .unwrap();
+        assert!(matches!(
+            crate::decode_notes::<Duration>(before_start.as_bytes()),
+            Err(crate::DecodeError::InvalidPosition)
+        ));
+    }
+}
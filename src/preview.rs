@@ -0,0 +1,533 @@
+/*!
+Audible timing previews, for checking sync without ArrowVortex
+
+[`render`] synthesizes a click at every note or beat and returns the result as a [`Wav`]. To hear
+the clicks over the song, decode it with [`Wav::decode`] and pass it to [`mix`]. Only
+uncompressed WAV files are supported.
+
+```rust
+use arrowvortex_clipboard::{preview, text, Row, TempoEvent, TempoEventKind};
+
+let notes = text::parse_notes("0 1000\n48 0100\n96 0010\n")?;
+let tempo_events = [TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 120.0 } }];
+
+let times = preview::click_times(&notes, &tempo_events, preview::Ticks::Notes);
+assert_eq!(times, [0.0, 0.5, 1.0]);
+
+let wav = preview::render(&notes, &tempo_events, &preview::PreviewOptions::default());
+// A few seconds of audio easily fit into a WAV file, so this cannot fail
+let wav_file = wav.encode().unwrap();
+assert_eq!(&wav_file[..4], b"RIFF");
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::timing::Timing;
+use crate::{Note, NoteKind, Row, TempoEvent, ROWS_PER_BEAT};
+use alloc::{string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+
+/// Error that occurs while decoding or encoding a WAV file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WavError {
+    /// Input ended unexpectedly
+    UnexpectedEof,
+    /// Input does not start with a RIFF WAVE header
+    MissingHeader,
+    /// A chunk that is required for decoding is missing
+    MissingChunk {
+        /// Identifier of the missing chunk, like `b"fmt "`
+        chunk: [u8; 4],
+    },
+    /// Samples are compressed or in a format that is not supported
+    UnsupportedFormat {
+        /// Format tag from the `fmt ` chunk
+        format: u16,
+        /// Size of a single sample in bits
+        bits_per_sample: u16,
+    },
+    /// Audio has too many samples or channels, or too high a sample rate, for the 32-bit sizes of
+    /// a WAV file
+    TooLarge,
+}
+
+impl core::fmt::Display for WavError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnexpectedEof => f.write_str("unexpected EOF"),
+            Self::MissingHeader => f.write_str("argument is not a WAV file"),
+            Self::MissingChunk { chunk } => write!(
+                f,
+                "WAV file has no \"{}\" chunk",
+                String::from_utf8_lossy(chunk).trim_end()
+            ),
+            Self::UnsupportedFormat {
+                format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported WAV format {} with {} bits per sample",
+                format, bits_per_sample
+            ),
+            Self::TooLarge => f.write_str("audio is too large for a WAV file"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WavError {}
+
+/// Uncompressed audio
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Wav {
+    /// Samples per second and channel
+    pub sample_rate: u32,
+    /// Number of channels, e.g. 2 for stereo
+    pub num_channels: u16,
+    /// Samples from -1 to 1, with the channels interleaved
+    pub samples: Vec<f32>,
+}
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, WavError> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(WavError::UnexpectedEof),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, WavError> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(WavError::UnexpectedEof),
+    }
+}
+
+impl Wav {
+    /// Decodes a WAV file with integer PCM samples of 8, 16, 24 or 32 bits, or float samples of 32
+    /// or 64 bits
+    pub fn decode(bytes: &[u8]) -> Result<Self, WavError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(WavError::MissingHeader);
+        }
+
+        let mut format_chunk = None;
+        let mut data_chunk = None;
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let len = read_u32(bytes, offset + 4)? as usize;
+            let start = offset + 8;
+            // Some encoders write a bogus length for the last chunk, so clamp instead of failing
+            let end = start.saturating_add(len).min(bytes.len());
+            match id {
+                b"fmt " => format_chunk = Some(&bytes[start..end]),
+                b"data" => data_chunk = Some(&bytes[start..end]),
+                _ => {}
+            }
+            // Chunks are padded to an even length
+            offset = end + (len & 1);
+        }
+        let format_chunk = format_chunk.ok_or(WavError::MissingChunk { chunk: *b"fmt " })?;
+        let data = data_chunk.ok_or(WavError::MissingChunk { chunk: *b"data" })?;
+
+        let mut format = read_u16(format_chunk, 0)?;
+        let num_channels = read_u16(format_chunk, 2)?;
+        let sample_rate = read_u32(format_chunk, 4)?;
+        let bits_per_sample = read_u16(format_chunk, 14)?;
+        if format == FORMAT_EXTENSIBLE {
+            // The first two bytes of the sub-format GUID are the actual format tag
+            format = read_u16(format_chunk, 24)?;
+        }
+
+        let unsupported = WavError::UnsupportedFormat {
+            format,
+            bits_per_sample,
+        };
+        if num_channels == 0 {
+            return Err(unsupported);
+        }
+        let samples = match (format, bits_per_sample) {
+            (FORMAT_PCM, 8) => data.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+            (FORMAT_PCM, 16) => data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            (FORMAT_PCM, 24) => data
+                .chunks_exact(3)
+                .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+                .collect(),
+            (FORMAT_PCM, 32) => data
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+                .collect(),
+            (FORMAT_FLOAT, 32) => data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            (FORMAT_FLOAT, 64) => data
+                .chunks_exact(8)
+                .map(|b| {
+                    f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+                })
+                .collect(),
+            _ => return Err(unsupported),
+        };
+
+        Ok(Self {
+            sample_rate,
+            num_channels,
+            samples,
+        })
+    }
+
+    /// Encodes the audio as a WAV file with 16-bit PCM samples
+    ///
+    /// Fails with [`WavError::TooLarge`] if the file would be larger than 4 GiB, or if the number
+    /// of channels or bytes per second don't fit into the header.
+    pub fn encode(&self) -> Result<Vec<u8>, WavError> {
+        let data_len = u32::try_from(self.samples.len())
+            .ok()
+            .and_then(|len| len.checked_mul(2))
+            .filter(|&len| len <= u32::MAX - 36)
+            .ok_or(WavError::TooLarge)?;
+        let block_align = self.num_channels.checked_mul(2).ok_or(WavError::TooLarge)?;
+        let byte_rate = self
+            .sample_rate
+            .checked_mul(block_align as u32)
+            .ok_or(WavError::TooLarge)?;
+
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16_u32.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        bytes.extend_from_slice(&self.num_channels.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&byte_rate.to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&16_u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for &sample in &self.samples {
+            let sample = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// Duration in seconds
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate == 0 || self.num_channels == 0 {
+            return 0.0;
+        }
+        (self.samples.len() / self.num_channels as usize) as f64 / self.sample_rate as f64
+    }
+}
+
+/// What to click on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ticks {
+    /// Every row with at least one note to be hit. Mines, fakes and notes skipped by warps are
+    /// left out
+    Notes,
+    /// Every beat, up to the last note
+    Beats,
+    /// Both notes and beats, with different click sounds
+    NotesAndBeats,
+}
+
+/// Settings for [`render`] and [`mix`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewOptions {
+    /// What to click on
+    pub ticks: Ticks,
+    /// Time of row 0 in the output, in seconds. Clicks before the start of the output are left
+    /// out
+    pub offset: f64,
+    /// Peak amplitude of each click, from 0 to 1
+    pub volume: f32,
+    /// Sample rate of the output of [`render`]. [`mix`] uses the sample rate of the song
+    pub sample_rate: u32,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            ticks: Ticks::Notes,
+            offset: 0.0,
+            volume: 0.5,
+            sample_rate: 44100,
+        }
+    }
+}
+
+/// Length of a single click in seconds
+const CLICK_SECONDS: f64 = 0.03;
+
+/// Longest audio that [`render`] produces, in seconds
+pub const MAX_RENDER_SECONDS: f64 = 60.0 * 60.0;
+
+/// Timestamps of notes or beats, relative to row 0
+struct Click {
+    time: f64,
+    frequency: f64,
+}
+
+fn clicks(notes: &[Note<Row>], tempo_events: &[TempoEvent], ticks: Ticks) -> Vec<Click> {
+    let timing = Timing::new(tempo_events);
+    let mut clicks = Vec::new();
+
+    if let Ticks::Notes | Ticks::NotesAndBeats = ticks {
+        let mut rows = notes
+            .iter()
+            .filter(|note| match note.kind {
+                NoteKind::Mine | NoteKind::Fake => false,
+                _ => !timing.is_warped(note.pos),
+            })
+            .map(|note| note.pos)
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+        rows.dedup();
+        clicks.extend(rows.into_iter().map(|row| Click {
+            time: timing.row_to_time(row),
+            frequency: 2000.0,
+        }));
+    }
+
+    if let Ticks::Beats | Ticks::NotesAndBeats = ticks {
+        let last_row = notes
+            .iter()
+            .map(|note| match note.kind {
+                NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => end_pos.0,
+                _ => note.pos.0,
+            })
+            .max()
+            .unwrap_or(0);
+        // Notes far apart, or slow tempos, would otherwise need billions of beats
+        clicks.extend(
+            (0..=last_row / ROWS_PER_BEAT)
+                .map(Row::from_beat)
+                .filter(|&row| !timing.is_warped(row))
+                .map(|row| Click {
+                    time: timing.row_to_time(row),
+                    frequency: 1000.0,
+                })
+                .take_while(|click| click.time <= MAX_RENDER_SECONDS),
+        );
+    }
+
+    // Times are infinite or NaN if the tempo events are; such clicks can't be placed anywhere
+    clicks.retain(|click| click.time.is_finite());
+    clicks.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    clicks
+}
+
+/// Returns the time in seconds of every click, relative to row 0
+///
+/// A note and a beat on the same row produce two clicks at the same time. Rows that extreme tempo
+/// events push to an infinite time are left out, and beats stop before the first one that is
+/// later than [`MAX_RENDER_SECONDS`].
+pub fn click_times(notes: &[Note<Row>], tempo_events: &[TempoEvent], ticks: Ticks) -> Vec<f64> {
+    clicks(notes, tempo_events, ticks)
+        .iter()
+        .map(|click| click.time)
+        .collect()
+}
+
+/// Adds clicks to `song`, on all channels
+///
+/// Clicks past the end of the song are cut off. Mixed samples are clamped to the range -1 to 1.
+pub fn mix(
+    notes: &[Note<Row>],
+    tempo_events: &[TempoEvent],
+    song: &mut Wav,
+    options: &PreviewOptions,
+) {
+    let num_channels = song.num_channels.max(1) as usize;
+    let num_frames = song.samples.len() / num_channels;
+    let sample_rate = song.sample_rate as f64;
+    let click_len = (CLICK_SECONDS * sample_rate) as usize;
+
+    for click in clicks(notes, tempo_events, options.ticks) {
+        let start = ((options.offset + click.time) * sample_rate).round();
+        // Also skips clicks at NaN, which fails every comparison
+        if !(start >= 0.0 && start < num_frames as f64) {
+            continue;
+        }
+        let start = start as usize;
+        for frame in start..start.saturating_add(click_len).min(num_frames) {
+            let t = (frame - start) as f64 / sample_rate;
+            // Decaying cosine, so that the click is at full volume on its very first sample
+            let envelope = (-t / (CLICK_SECONDS / 5.0)).exp();
+            let value = (2.0 * core::f64::consts::PI * click.frequency * t).cos() * envelope;
+            let value = value as f32 * options.volume;
+            for sample in &mut song.samples[frame * num_channels..(frame + 1) * num_channels] {
+                *sample = (*sample + value).clamp(-1.0, 1.0);
+            }
+        }
+    }
+}
+
+/// Renders clicks into silent mono audio that lasts until the end of the last click
+///
+/// The audio is cut off after [`MAX_RENDER_SECONDS`].
+pub fn render(notes: &[Note<Row>], tempo_events: &[TempoEvent], options: &PreviewOptions) -> Wav {
+    let end = click_times(notes, tempo_events, options.ticks)
+        .last()
+        .map_or(0.0, |&time| options.offset + time + CLICK_SECONDS);
+    // A NaN end stays NaN, which the cast turns into zero samples
+    let end = end.clamp(0.0, MAX_RENDER_SECONDS);
+    let num_samples = (end * options.sample_rate as f64).ceil() as usize;
+
+    let mut wav = Wav {
+        sample_rate: options.sample_rate,
+        num_channels: 1,
+        samples: vec![0.0; num_samples],
+    };
+    mix(notes, tempo_events, &mut wav, options);
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempoEventKind;
+
+    /// Sample indices at which a click starts, i.e. where silence is followed by sound
+    fn click_starts(samples: &[f32], num_channels: usize) -> Vec<usize> {
+        let mut starts = Vec::new();
+        let mut silent_frames = usize::MAX;
+        for (frame, chunk) in samples.chunks(num_channels).enumerate() {
+            if chunk.iter().all(|&sample| sample == 0.0) {
+                silent_frames = silent_frames.saturating_add(1);
+            } else {
+                if silent_frames > 100 {
+                    starts.push(frame);
+                }
+                silent_frames = 0;
+            }
+        }
+        starts
+    }
+
+    #[test]
+    fn test_click_offsets() {
+        let notes =
+            crate::text::parse_notes("0 1000\n48 0100\n96 2000\n192 3010\n240 0001\n").unwrap();
+        let tempo_events = [
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 120.0 },
+            },
+            TempoEvent {
+                row: Row(48),
+                kind: TempoEventKind::Stop { time: 0.25 },
+            },
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Delay { time: 0.125 },
+            },
+            TempoEvent {
+                row: Row(192),
+                kind: TempoEventKind::Warp {
+                    num_skipped_rows: 48,
+                },
+            },
+        ];
+
+        // The note at row 240 is where the warp lands, so it is hit right after the one at 192
+        let expected = [0.0, 0.5, 1.375, 2.375, 2.375];
+        let times = click_times(&notes, &tempo_events, Ticks::Notes);
+        assert_eq!(times, &expected[..]);
+
+        let options = PreviewOptions {
+            offset: 0.5,
+            sample_rate: 8000,
+            ..Default::default()
+        };
+        let wav = render(&notes, &tempo_events, &options);
+        let expected_starts = [0.0, 0.5, 1.375, 2.375]
+            .iter()
+            .map(|time| ((0.5 + time) * 8000.0) as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(click_starts(&wav.samples, 1), expected_starts);
+        assert!((wav.duration() - (0.5 + 2.375 + CLICK_SECONDS)).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_mix_over_wav() {
+        let notes = crate::text::parse_notes("0 1000\n96 0100\n").unwrap();
+        let mut song = Wav {
+            sample_rate: 1000,
+            num_channels: 2,
+            samples: vec![0.0; 2 * 2000],
+        };
+        // Round trip through a file, as if the song was loaded from disk
+        song = Wav::decode(&song.encode().unwrap()).unwrap();
+        assert_eq!((song.sample_rate, song.num_channels), (1000, 2));
+
+        let options = PreviewOptions {
+            ticks: Ticks::NotesAndBeats,
+            offset: 0.25,
+            ..Default::default()
+        };
+        mix(&notes, &[], &mut song, &options);
+        // Beats at 0, 0.5 and 1 seconds, and notes on the first and last of them
+        assert_eq!(click_starts(&song.samples, 2), [250, 750, 1250]);
+        assert_eq!(song.samples[500], song.samples[501]);
+
+        assert_eq!(
+            Wav::decode(b"RIFF\0\0\0\0WAVE"),
+            Err(WavError::MissingChunk { chunk: *b"fmt " })
+        );
+        let too_many_channels = Wav {
+            num_channels: u16::MAX,
+            ..song
+        };
+        assert_eq!(too_many_channels.encode(), Err(WavError::TooLarge));
+    }
+
+    #[test]
+    fn test_extreme_timing() {
+        let notes = crate::text::parse_notes("0 1000\n12 0100\n96 0010\n").unwrap();
+        let tempo_events = [
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 120.0 },
+            },
+            TempoEvent {
+                row: Row(24),
+                kind: TempoEventKind::Bpm { bpm: 1e-320 },
+            },
+        ];
+        // The notes from row 24 on are infinitely far away
+        let times = click_times(&notes, &tempo_events, Ticks::Notes);
+        assert_eq!(times, [0.0, 0.125]);
+
+        let options = PreviewOptions {
+            offset: 1e300,
+            sample_rate: 1000,
+            ..Default::default()
+        };
+        let wav = render(&notes, &tempo_events, &options);
+        assert_eq!(wav.duration(), MAX_RENDER_SECONDS);
+        assert!(wav.samples.iter().all(|&sample| sample == 0.0));
+
+        // Beats stop at the render limit, at 0.5 seconds per beat
+        let notes = [Note {
+            pos: Row(u64::MAX),
+            column: 0,
+            kind: NoteKind::Tap,
+        }];
+        let times = click_times(&notes, &tempo_events[..1], Ticks::Beats);
+        assert_eq!(times.len() as f64, MAX_RENDER_SECONDS * 2.0 + 1.0);
+    }
+}
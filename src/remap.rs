@@ -0,0 +1,422 @@
+/*!
+Column remapping, for porting patterns between game modes
+
+A [`ColumnMap`] says which target columns the notes of each source column go to. Maps can be given
+explicitly or derived from the game modes involved: [`ColumnMap::scale`] spreads or folds columns
+evenly, like 4K onto 7K, [`ColumnMap::both_pads`] copies a single pad onto both pads of a double
+mode, and [`ColumnMap::by_lanes`] matches lanes by direction, like pump onto dance.
+
+[`remap`] applies a map. Notes that end up on the same spot or inside a hold are resolved by a
+[`CollisionPolicy`], and every note that couldn't be placed as mapped is reported as a
+[`RemapIssue`].
+
+```rust
+use arrowvortex_clipboard::remap::{self, CollisionPolicy, ColumnMap, RemapIssueKind};
+use arrowvortex_clipboard::text;
+
+// Fold 7K onto 4K: the jump on the two left-most keys lands on a single column
+let notes = text::parse_notes("0 1100000\n12 0000001")?;
+let remapped = remap::remap(&notes, &ColumnMap::scale(7, 4), CollisionPolicy::Merge);
+
+assert_eq!(remapped.notes, text::parse_notes("0 1000\n12 0001")?);
+assert_eq!(remapped.issues[0].note, notes[1]);
+assert_eq!(remapped.issues[0].kind, RemapIssueKind::Merged { column: 0 });
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::mode::{GameMode, Lane};
+use crate::{Note, NoteKind};
+use alloc::{vec, vec::Vec};
+
+/// Target columns for every source column
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ColumnMap {
+    /// Target columns of each source column. Notes in source columns beyond the end, or with no
+    /// target columns, are dropped. Notes in source columns with several target columns are copied
+    pub targets: Vec<Vec<u8>>,
+}
+
+impl ColumnMap {
+    /// Maps source column `i` onto column `columns[i]`
+    ///
+    /// ```rust
+    /// use arrowvortex_clipboard::remap::ColumnMap;
+    ///
+    /// // Swap left and right
+    /// let mirror = ColumnMap::new(&[3, 1, 2, 0]);
+    /// assert_eq!(mirror.targets[0], [3]);
+    /// ```
+    pub fn new(columns: &[u8]) -> Self {
+        Self {
+            targets: columns.iter().map(|&column| vec![column]).collect(),
+        }
+    }
+
+    /// Spreads or folds `from_columns` columns evenly across `to_columns` columns, keeping their
+    /// order. Spreading 4 onto 7 columns uses every other column; folding 7 onto 4 columns merges
+    /// neighbouring columns
+    pub fn scale(from_columns: u8, to_columns: u8) -> Self {
+        let targets = (0..from_columns as u32)
+            .map(|column| {
+                // Map the center of each source column into the target range
+                let target = (2 * column + 1) * to_columns as u32 / (2 * from_columns as u32);
+                vec![target as u8]
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Copies every column of `from` onto each pad of `to`, like dance-single onto both pads of
+    /// dance-double
+    ///
+    /// If `mirror` is set, the pads after the first get a horizontally mirrored copy, so left
+    /// becomes right while up and down stay. Columns without a counterpart on a pad are left out
+    /// there.
+    pub fn both_pads(from: GameMode, to: GameMode, mirror: bool) -> Self {
+        let targets = from
+            .lanes()
+            .iter()
+            .enumerate()
+            .map(|(column, &lane)| {
+                (0..to.num_pads())
+                    .filter_map(|pad| {
+                        let pad_columns = to.pad_columns(pad);
+                        if mirror && pad > 0 {
+                            find_lane(to, pad, mirrored(lane))
+                        } else if (column as u8) < pad_columns.len() as u8 {
+                            Some(pad_columns.start + column as u8)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Maps every column of `from` onto the column of `to` with the same lane, on the same pad if
+    /// `to` has enough pads
+    ///
+    /// Lanes that `to` doesn't have go to the lane with the next closest direction, preferring
+    /// clockwise. Pump's diagonals map onto dance's arrows this way: down-left becomes left,
+    /// up-left becomes up and so on. Lanes without a direction, like pump's center panel, are only
+    /// mapped onto the same lane.
+    ///
+    /// ```rust
+    /// use arrowvortex_clipboard::{mode::GameMode, remap::ColumnMap};
+    ///
+    /// let map = ColumnMap::by_lanes(GameMode::PumpSingle, GameMode::DanceSingle);
+    /// assert_eq!(map.targets, [vec![0], vec![2], vec![], vec![3], vec![1]]);
+    /// ```
+    pub fn by_lanes(from: GameMode, to: GameMode) -> Self {
+        let targets = from
+            .lanes()
+            .iter()
+            .enumerate()
+            .map(|(column, &lane)| {
+                let pad = from
+                    .pad_of(column as u8)
+                    .unwrap_or(0)
+                    .min(to.num_pads() - 1);
+                nearest_lane(to, pad, lane).into_iter().collect()
+            })
+            .collect();
+        Self { targets }
+    }
+
+    /// Number of columns that the map writes into
+    ///
+    /// This is a `usize` because a map that writes into column 255 covers 256 columns.
+    pub fn num_columns(&self) -> usize {
+        self.targets
+            .iter()
+            .flatten()
+            .map(|&column| column as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn find_lane(mode: GameMode, pad: u8, lane: Lane) -> Option<u8> {
+    mode.pad_columns(pad)
+        .find(|&column| mode.lane(column) == Some(lane))
+}
+
+/// Lane with the given direction in eighth turns clockwise from upwards
+fn lane_from_eighth_turns(eighth_turns: u8) -> Lane {
+    [
+        Lane::Up,
+        Lane::UpRight,
+        Lane::Right,
+        Lane::DownRight,
+        Lane::Down,
+        Lane::DownLeft,
+        Lane::Left,
+        Lane::UpLeft,
+    ][eighth_turns as usize % 8]
+}
+
+fn mirrored(lane: Lane) -> Lane {
+    match lane.eighth_turns() {
+        Some(eighth_turns) => lane_from_eighth_turns(8 - eighth_turns),
+        None => lane,
+    }
+}
+
+fn nearest_lane(mode: GameMode, pad: u8, lane: Lane) -> Option<u8> {
+    let eighth_turns = match lane.eighth_turns() {
+        Some(eighth_turns) => eighth_turns,
+        None => return find_lane(mode, pad, lane),
+    };
+    // Exact match first, then ever larger deviations, clockwise before counterclockwise
+    [0, 1, 7, 2, 6, 3, 5, 4]
+        .iter()
+        .find_map(|&offset| find_lane(mode, pad, lane_from_eighth_turns(eighth_turns + offset)))
+}
+
+/// What [`remap`] does with a note whose target spot is already taken, either by another note at
+/// the same position or by a hold or roll that is still going on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CollisionPolicy {
+    /// Drop the note, keeping the note that was there first
+    Merge,
+    /// Move the note to the nearest free column, preferring the left side. If there is none, drop
+    /// it like [`CollisionPolicy::Merge`]
+    Relocate,
+}
+
+/// Type of a [`RemapIssue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RemapIssueKind {
+    /// The note's column has no target column. The note was dropped
+    Unmapped,
+    /// Another note is at the same position in the target column. The note was dropped
+    Merged {
+        /// The target column
+        column: u8,
+    },
+    /// The target column is covered by a hold or roll at the note's position. The note was
+    /// dropped
+    InsideHold {
+        /// The target column
+        column: u8,
+    },
+    /// The target spot was taken, so the note was placed into another column
+    Relocated {
+        /// The column that the note was placed into instead
+        column: u8,
+    },
+}
+
+/// A note that [`remap`] could not place as mapped
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemapIssue<P> {
+    /// The note before remapping
+    pub note: Note<P>,
+    /// What happened to the note
+    pub kind: RemapIssueKind,
+}
+
+/// Result of [`remap`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Remapped<P> {
+    /// The remapped notes, sorted by position and column
+    pub notes: Vec<Note<P>>,
+    /// Notes that were dropped or relocated, sorted by position
+    pub issues: Vec<RemapIssue<P>>,
+}
+
+/// Notes that win a collision come first: holds and rolls, longer ones first, then taps, lifts,
+/// fakes and mines
+fn collision_order<P: Ord>(a: &Note<P>, b: &Note<P>) -> core::cmp::Ordering {
+    fn rank<P>(kind: &NoteKind<P>) -> u8 {
+        match kind {
+            NoteKind::Hold { .. } | NoteKind::Roll { .. } => 0,
+            NoteKind::Tap => 1,
+            NoteKind::Lift => 2,
+            NoteKind::Fake => 3,
+            NoteKind::Mine => 4,
+        }
+    }
+    fn end_pos<P>(note: &Note<P>) -> Option<&P> {
+        match &note.kind {
+            NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => Some(end_pos),
+            _ => None,
+        }
+    }
+    rank(&a.kind)
+        .cmp(&rank(&b.kind))
+        .then_with(|| end_pos(b).cmp(&end_pos(a)))
+}
+
+/// Moves the notes into the columns given by `map`
+///
+/// Notes may be given in any order. Collisions are resolved in order of position; at the same
+/// position, holds and rolls win over taps, which win over lifts, fakes and mines.
+pub fn remap<P: Ord + Clone>(
+    notes: &[Note<P>],
+    map: &ColumnMap,
+    policy: CollisionPolicy,
+) -> Remapped<P> {
+    let mut sorted = notes.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| (&a.pos, a.column).cmp(&(&b.pos, b.column)));
+
+    let num_columns = map.num_columns();
+    let mut output = Vec::new();
+    let mut issues = Vec::new();
+    // Position at which a hold or roll in each target column ends
+    let mut hold_ends: Vec<Option<P>> = vec![None; num_columns];
+    let mut group_start = 0;
+    while group_start < sorted.len() {
+        let pos = &sorted[group_start].pos;
+        let group_len = sorted[group_start..]
+            .iter()
+            .take_while(|note| &note.pos == pos)
+            .count();
+
+        // Every copy of every note at this position, with its mapped column
+        let mut candidates = Vec::new();
+        for &note in &sorted[group_start..group_start + group_len] {
+            match map.targets.get(note.column as usize) {
+                Some(targets) if !targets.is_empty() => {
+                    candidates.extend(targets.iter().map(|&column| (note, column)))
+                }
+                _ => issues.push(RemapIssue {
+                    note: note.clone(),
+                    kind: RemapIssueKind::Unmapped,
+                }),
+            }
+        }
+        candidates.sort_by(|(a, _), (b, _)| collision_order(a, b));
+
+        let mut occupied = vec![false; num_columns];
+        for (note, column) in candidates {
+            let is_held = |column: usize| match &hold_ends[column] {
+                Some(end_pos) => end_pos >= pos,
+                None => false,
+            };
+            let is_free = |column: usize| !occupied[column] && !is_held(column);
+
+            let mut placed_column = column;
+            if !is_free(column as usize) {
+                let relocated = match policy {
+                    CollisionPolicy::Merge => None,
+                    CollisionPolicy::Relocate => (1..num_columns)
+                        .flat_map(|distance| {
+                            let left = (column as usize).checked_sub(distance);
+                            let right = Some(column as usize + distance);
+                            left.into_iter().chain(right)
+                        })
+                        .find(|&candidate| candidate < num_columns && is_free(candidate)),
+                };
+                match relocated {
+                    Some(relocated) => {
+                        placed_column = relocated as u8;
+                        issues.push(RemapIssue {
+                            note: note.clone(),
+                            kind: RemapIssueKind::Relocated {
+                                column: placed_column,
+                            },
+                        });
+                    }
+                    None => {
+                        let kind = if occupied[column as usize] {
+                            RemapIssueKind::Merged { column }
+                        } else {
+                            RemapIssueKind::InsideHold { column }
+                        };
+                        issues.push(RemapIssue {
+                            note: note.clone(),
+                            kind,
+                        });
+                        continue;
+                    }
+                }
+            }
+
+            occupied[placed_column as usize] = true;
+            if let NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } = &note.kind {
+                hold_ends[placed_column as usize] = Some(end_pos.clone());
+            }
+            output.push(Note {
+                pos: note.pos.clone(),
+                column: placed_column,
+                kind: note.kind.clone(),
+            });
+        }
+        group_start += group_len;
+    }
+
+    output.sort_by(|a, b| (&a.pos, a.column).cmp(&(&b.pos, b.column)));
+    Remapped {
+        notes: output,
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse_notes;
+
+    #[test]
+    fn test_maps() {
+        assert_eq!(
+            ColumnMap::scale(4, 7),
+            ColumnMap::new(&[0, 2, 4, 6]),
+            "4K spreads onto every other 7K column"
+        );
+        assert_eq!(
+            ColumnMap::scale(7, 4),
+            ColumnMap::new(&[0, 0, 1, 2, 2, 3, 3])
+        );
+
+        let double = ColumnMap::both_pads(GameMode::DanceSingle, GameMode::DanceDouble, false);
+        assert_eq!(double.targets, [[0, 4], [1, 5], [2, 6], [3, 7]]);
+        let mirrored = ColumnMap::both_pads(GameMode::DanceSingle, GameMode::DanceDouble, true);
+        assert_eq!(mirrored.targets, [[0, 7], [1, 5], [2, 6], [3, 4]]);
+
+        let dance_to_pump = ColumnMap::by_lanes(GameMode::DanceSingle, GameMode::PumpSingle);
+        assert_eq!(dance_to_pump.targets, [[1], [0], [3], [4]]);
+        let doubles = ColumnMap::by_lanes(GameMode::PumpDouble, GameMode::DanceDouble);
+        assert_eq!(doubles.targets[5], [4]);
+
+        assert_eq!(double.num_columns(), 8);
+        assert_eq!(ColumnMap::new(&[3, 255]).num_columns(), 256);
+    }
+
+    #[test]
+    fn test_collisions() {
+        let notes = parse_notes("0 2100\n12 0010\n24 3001").unwrap();
+        let fold = ColumnMap::new(&[0, 0, 0, 1]);
+
+        let merged = remap(&notes, &fold, CollisionPolicy::Merge);
+        assert_eq!(merged.notes, parse_notes("0 20\n24 31").unwrap());
+        assert_eq!(
+            merged.issues,
+            [
+                RemapIssue {
+                    note: notes[1].clone(),
+                    kind: RemapIssueKind::Merged { column: 0 },
+                },
+                RemapIssue {
+                    note: notes[2].clone(),
+                    kind: RemapIssueKind::InsideHold { column: 0 },
+                },
+            ]
+        );
+
+        let relocated = remap(&notes, &fold, CollisionPolicy::Relocate);
+        assert_eq!(relocated.notes, parse_notes("0 21\n12 01\n24 31").unwrap());
+        assert_eq!(
+            relocated.issues[0].kind,
+            RemapIssueKind::Relocated { column: 1 }
+        );
+
+        let unmapped = remap(&notes, &ColumnMap::new(&[0]), CollisionPolicy::Merge);
+        assert_eq!(unmapped.notes, parse_notes("0 2\n24 3").unwrap());
+        assert_eq!(unmapped.issues.len(), 3);
+    }
+}
@@ -0,0 +1,368 @@
+//! Layout shared between the image renderers. The chart is laid out once as a list of simple
+//! shapes, which the renderers then translate into their respective formats
+
+use super::{lane, measure_starts, starts_pad, Snap};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::mode::{GameMode, Lane};
+use crate::{Note, NoteKind, Row, TempoEvent, TempoEventKind, ROWS_PER_BEAT};
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
+
+/// Settings for the image renderers, [`svg::render`](super::svg::render) and `png::render`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Width of each column in pixels
+    pub lane_width: u32,
+    /// Vertical distance between two beats in pixels. Lowered as far as needed to keep the image
+    /// within [`MAX_IMAGE_HEIGHT`] pixels
+    pub pixels_per_beat: u32,
+    /// Minimum number of columns to draw
+    pub num_columns: u8,
+    /// Game mode whose lanes to draw, with separators between pads. If `None`, four and eight
+    /// columns are drawn as dance arrows and everything else as circles
+    pub mode: Option<GameMode>,
+    /// Whether to annotate BPM changes, stops, delays and warps beside the lanes
+    pub annotations: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            lane_width: 48,
+            pixels_per_beat: 64,
+            num_columns: 4,
+            mode: None,
+            annotations: true,
+        }
+    }
+}
+
+/// Tallest image drawn by the image renderers. Longer charts are squeezed vertically to fit
+pub const MAX_IMAGE_HEIGHT: u32 = 1 << 15;
+
+pub(crate) enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [u8; 3],
+        opacity: f32,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: [u8; 3],
+        opacity: f32,
+    },
+    Polygon {
+        points: Vec<(f32, f32)>,
+        color: [u8; 3],
+        opacity: f32,
+    },
+    /// `y` is the vertical center of the text
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        color: [u8; 3],
+        content: String,
+    },
+}
+
+pub(crate) struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub background: [u8; 3],
+    pub shapes: Vec<Shape>,
+}
+
+const MARGIN: f32 = 16.0;
+const ANNOTATION_WIDTH: f32 = 112.0;
+const TEXT_SIZE: f32 = 10.0;
+/// Beat and measure lines closer than this many pixels would blur together, so they're thinned out
+const MIN_LINE_SPACING: f32 = 4.0;
+
+const LANE_COLORS: [[u8; 3]; 2] = [[0x1C, 0x1C, 0x22], [0x24, 0x24, 0x2C]];
+const BEAT_LINE_COLOR: [u8; 3] = [0x50, 0x50, 0x5A];
+const MEASURE_LINE_COLOR: [u8; 3] = [0xC8, 0xC8, 0xD2];
+const HOLD_COLOR: [u8; 3] = [0x32, 0xC8, 0x32];
+const ROLL_COLOR: [u8; 3] = [0xF0, 0xDC, 0x1E];
+const MINE_COLOR: [u8; 3] = [0x5A, 0x5A, 0x5A];
+const MINE_CORE_COLOR: [u8; 3] = [0xE0, 0x1E, 0x1E];
+
+/// Arrow pointing upwards, centered on the origin with a radius of 1
+const ARROW: [(f32, f32); 7] = [
+    (0.0, -1.0),
+    (1.0, 0.0),
+    (0.4, 0.0),
+    (0.4, 1.0),
+    (-0.4, 1.0),
+    (-0.4, 0.0),
+    (-1.0, 0.0),
+];
+
+fn arrow(x: f32, y: f32, radius: f32, lane: Option<Lane>) -> Option<Vec<(f32, f32)>> {
+    // Lanes without a direction, like pump's center panel, get circles
+    let eighth_turns = lane?.eighth_turns()?;
+    let points = ARROW
+        .iter()
+        .map(|&(mut px, mut py)| {
+            for _ in 0..eighth_turns / 2 {
+                let turned = (-py, px);
+                px = turned.0;
+                py = turned.1;
+            }
+            if eighth_turns % 2 == 1 {
+                let s = core::f32::consts::FRAC_1_SQRT_2;
+                let turned = ((px - py) * s, (px + py) * s);
+                px = turned.0;
+                py = turned.1;
+            }
+            (x + px * radius, y + py * radius)
+        })
+        .collect();
+    Some(points)
+}
+
+/// Formats a number with at most three decimal places and no trailing zeros
+fn format_number(n: f64) -> String {
+    let formatted = format!("{:.3}", n);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_owned()
+}
+
+fn annotation(kind: &TempoEventKind) -> Option<(String, [u8; 3])> {
+    Some(match *kind {
+        TempoEventKind::Bpm { bpm } => (format!("{} BPM", format_number(bpm)), [0x5A, 0xA0, 0xFF]),
+        TempoEventKind::Stop { time } => {
+            (format!("STOP {}", format_number(time)), [0xFF, 0x64, 0x64])
+        }
+        TempoEventKind::Delay { time } => {
+            (format!("DELAY {}", format_number(time)), [0xFF, 0xA0, 0x3C])
+        }
+        TempoEventKind::Warp { num_skipped_rows } => {
+            (format!("WARP {}", num_skipped_rows), [0xC8, 0x78, 0xFF])
+        }
+        _ => return None,
+    })
+}
+
+pub(crate) fn layout(
+    notes: &[Note<Row>],
+    tempo_events: &[TempoEvent],
+    options: &ImageOptions,
+) -> Canvas {
+    let num_columns = notes
+        .iter()
+        .map(|note| note.column as usize + 1)
+        .max()
+        .unwrap_or(0)
+        .max(options.num_columns as usize)
+        .max(options.mode.map_or(0, GameMode::num_columns) as usize);
+    let end_pos = |note: &Note<Row>| match note.kind {
+        NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => end_pos.max(note.pos).0,
+        _ => note.pos.0,
+    };
+    let note_range = notes
+        .iter()
+        .map(|note| note.pos.0)
+        .min()
+        .zip(notes.iter().map(end_pos).max());
+    let tempo_range = tempo_events
+        .iter()
+        .map(|event| event.row.0)
+        .min()
+        .zip(tempo_events.iter().map(|event| event.row.0).max());
+    // Tempo events only extend the chart if there are no notes to show
+    let (first_row, last_row) = note_range.or(tempo_range).unwrap_or((0, 0));
+
+    let start_row = measure_starts(tempo_events, first_row, first_row, 1)
+        .first()
+        .map_or(first_row, |&(_, row)| row);
+    // Always show at least one full beat after the last note, unless rows run out
+    let end_row = (last_row / ROWS_PER_BEAT + 1)
+        .checked_mul(ROWS_PER_BEAT)
+        .unwrap_or(last_row);
+
+    let lane_width = options.lane_width as f32;
+    let max_chart_height = MAX_IMAGE_HEIGHT as f32 - MARGIN * 2.0 - 1.0;
+    let pixels_per_row = (options.pixels_per_beat as f32 / ROWS_PER_BEAT as f32)
+        .min(max_chart_height / (end_row - start_row).max(1) as f32);
+    let rows_per_measure_line = (MIN_LINE_SPACING / pixels_per_row).ceil() as u64;
+    let measures = measure_starts(tempo_events, first_row, last_row, rows_per_measure_line);
+    let lanes_width = lane_width * num_columns as f32;
+    let y_of = |row: u64| MARGIN + (row - start_row) as f32 * pixels_per_row;
+    let x_of = |column: u8| MARGIN + (column as f32 + 0.5) * lane_width;
+
+    let annotation_width = if options.annotations {
+        ANNOTATION_WIDTH
+    } else {
+        0.0
+    };
+    let width = MARGIN * 2.0 + lanes_width + annotation_width;
+    let height = y_of(end_row) + MARGIN;
+    let mut shapes = Vec::new();
+
+    for column in 0..num_columns {
+        shapes.push(Shape::Rect {
+            x: MARGIN + column as f32 * lane_width,
+            y: MARGIN,
+            width: lane_width,
+            height: y_of(end_row) - MARGIN,
+            color: LANE_COLORS[column % 2],
+            opacity: 1.0,
+        });
+        if starts_pad(options.mode, column as u8) {
+            shapes.push(Shape::Rect {
+                x: MARGIN + column as f32 * lane_width - 1.0,
+                y: MARGIN,
+                width: 2.0,
+                height: y_of(end_row) - MARGIN,
+                color: MEASURE_LINE_COLOR,
+                opacity: 1.0,
+            });
+        }
+    }
+
+    // Beat lines are left out entirely when too dense, since they would cover the lanes
+    if pixels_per_row * ROWS_PER_BEAT as f32 >= MIN_LINE_SPACING {
+        let first_beat = start_row / ROWS_PER_BEAT + (start_row % ROWS_PER_BEAT != 0) as u64;
+        for beat in first_beat..=end_row / ROWS_PER_BEAT {
+            shapes.push(Shape::Rect {
+                x: MARGIN,
+                y: y_of(beat * ROWS_PER_BEAT),
+                width: lanes_width,
+                height: 1.0,
+                color: BEAT_LINE_COLOR,
+                opacity: 1.0,
+            });
+        }
+    }
+    for &(_, measure_start) in &measures {
+        shapes.push(Shape::Rect {
+            x: MARGIN,
+            y: y_of(measure_start) - 1.0,
+            width: lanes_width,
+            height: 2.0,
+            color: MEASURE_LINE_COLOR,
+            opacity: 1.0,
+        });
+    }
+
+    let radius = lane_width * 0.4;
+    for note in notes {
+        let body_color = match note.kind {
+            NoteKind::Hold { .. } => HOLD_COLOR,
+            NoteKind::Roll { .. } => ROLL_COLOR,
+            _ => continue,
+        };
+        shapes.push(Shape::Rect {
+            x: x_of(note.column) - lane_width * 0.3,
+            y: y_of(note.pos.0),
+            width: lane_width * 0.6,
+            height: y_of(end_pos(note)) - y_of(note.pos.0) + radius * 0.5,
+            color: body_color,
+            opacity: 0.6,
+        });
+    }
+
+    for note in notes {
+        let (x, y) = (x_of(note.column), y_of(note.pos.0));
+        let snap_color = Snap::from_row(note.pos).color();
+        match note.kind {
+            NoteKind::Mine => {
+                shapes.push(Shape::Circle {
+                    x,
+                    y,
+                    radius: radius * 0.8,
+                    color: MINE_COLOR,
+                    opacity: 1.0,
+                });
+                shapes.push(Shape::Circle {
+                    x,
+                    y,
+                    radius: radius * 0.35,
+                    color: MINE_CORE_COLOR,
+                    opacity: 1.0,
+                });
+            }
+            NoteKind::Lift => shapes.push(Shape::Rect {
+                x: x - radius,
+                y: y - radius * 0.3,
+                width: radius * 2.0,
+                height: radius * 0.6,
+                color: snap_color,
+                opacity: 1.0,
+            }),
+            _ => {
+                let opacity = if note.kind == NoteKind::Fake {
+                    0.35
+                } else {
+                    1.0
+                };
+                shapes.push(
+                    match arrow(x, y, radius, lane(options.mode, note.column, num_columns)) {
+                        Some(points) => Shape::Polygon {
+                            points,
+                            color: snap_color,
+                            opacity,
+                        },
+                        None => Shape::Circle {
+                            x,
+                            y,
+                            radius,
+                            color: snap_color,
+                            opacity,
+                        },
+                    },
+                );
+            }
+        }
+    }
+
+    if options.annotations {
+        // Annotations on the same row are stacked below each other
+        let mut annotations_per_row = alloc::collections::BTreeMap::new();
+        for event in tempo_events {
+            let row = event.row.0;
+            if row < start_row || row > end_row {
+                continue;
+            }
+            let (content, color) = match annotation(&event.kind) {
+                Some(annotation) => annotation,
+                None => continue,
+            };
+            let x = MARGIN + lanes_width;
+            let stack_index = annotations_per_row.entry(row).or_insert(0);
+            let text_y = y_of(row) + *stack_index as f32 * TEXT_SIZE * 1.5;
+            *stack_index += 1;
+            shapes.push(Shape::Rect {
+                x,
+                y: y_of(row),
+                width: 8.0,
+                height: 1.0,
+                color,
+                opacity: 1.0,
+            });
+            shapes.push(Shape::Text {
+                x: x + 12.0,
+                y: text_y,
+                size: TEXT_SIZE,
+                color,
+                content,
+            });
+        }
+    }
+
+    Canvas {
+        width: width.ceil() as u32,
+        height: height.ceil() as u32,
+        background: [0x10, 0x10, 0x14],
+        shapes,
+    }
+}
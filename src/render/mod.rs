@@ -0,0 +1,229 @@
+/*!
+Visualizations of note selections
+
+- [`terminal`] draws notes as text, optionally colored with ANSI escapes
+- [`svg`] draws notes into an SVG image
+- `png` draws notes into a PNG image (requires the `png` feature)
+*/
+
+mod image;
+pub use image::{ImageOptions, MAX_IMAGE_HEIGHT};
+
+#[cfg(feature = "png")]
+pub mod png;
+pub mod svg;
+pub mod terminal;
+
+use crate::beat::{BeatError, Fraction, MeasurePosition, Measures};
+use crate::mode::{GameMode, Lane};
+use crate::{Row, TempoEvent, ROWS_PER_BEAT};
+use alloc::vec::Vec;
+
+/// Rhythmic quantization of a row, as commonly shown by note colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Snap {
+    /// Quarter notes; on the beat
+    Fourth,
+    /// Eighth notes
+    Eighth,
+    /// Triplets
+    Twelfth,
+    /// Sixteenth notes
+    Sixteenth,
+    /// Sextuplets
+    TwentyFourth,
+    /// Thirty-second notes
+    ThirtySecond,
+    /// Forty-eighth notes
+    FortyEighth,
+    /// Sixty-fourth notes
+    SixtyFourth,
+    /// Ninety-sixth notes
+    NinetySixth,
+    /// Hundred-ninety-second notes; the finest snap ArrowVortex rows can express
+    HundredNinetySecond,
+}
+
+impl Snap {
+    /// Returns the snap of the given row
+    pub fn from_row(row: Row) -> Self {
+        match ROWS_PER_BEAT / gcd(row.0 % ROWS_PER_BEAT, ROWS_PER_BEAT) {
+            1 => Self::Fourth,
+            2 => Self::Eighth,
+            3 => Self::Twelfth,
+            4 => Self::Sixteenth,
+            6 => Self::TwentyFourth,
+            8 => Self::ThirtySecond,
+            12 => Self::FortyEighth,
+            16 => Self::SixtyFourth,
+            24 => Self::NinetySixth,
+            _ => Self::HundredNinetySecond,
+        }
+    }
+
+    /// Returns the number of notes of this snap that fit into a 4/4 measure, e.g. 16 for
+    /// [`Snap::Sixteenth`]
+    pub fn denominator(self) -> u32 {
+        match self {
+            Self::Fourth => 4,
+            Self::Eighth => 8,
+            Self::Twelfth => 12,
+            Self::Sixteenth => 16,
+            Self::TwentyFourth => 24,
+            Self::ThirtySecond => 32,
+            Self::FortyEighth => 48,
+            Self::SixtyFourth => 64,
+            Self::NinetySixth => 96,
+            Self::HundredNinetySecond => 192,
+        }
+    }
+
+    /// Returns the note color of this snap as RGB, following the common noteskin convention
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Self::Fourth => [0xE0, 0x1E, 0x1E],
+            Self::Eighth => [0x1E, 0x5A, 0xE6],
+            Self::Twelfth => [0xA0, 0x32, 0xDC],
+            Self::Sixteenth => [0xF0, 0xDC, 0x1E],
+            Self::TwentyFourth => [0xF0, 0x64, 0xC8],
+            Self::ThirtySecond => [0xF5, 0x8C, 0x1E],
+            Self::FortyEighth => [0x1E, 0xD2, 0xE6],
+            Self::SixtyFourth => [0x32, 0xC8, 0x32],
+            Self::NinetySixth | Self::HundredNinetySecond => [0x96, 0x96, 0x96],
+        }
+    }
+}
+
+/// Lane of the given column. Without a game mode, multiples of four columns up to eight are
+/// assumed to be dance layouts and everything else has no lanes
+pub(crate) fn lane(mode: Option<GameMode>, column: u8, num_columns: usize) -> Option<Lane> {
+    match mode {
+        Some(mode) => mode.lane(column),
+        None if num_columns % 4 == 0 && num_columns <= 8 => GameMode::DanceSingle.lane(column % 4),
+        None => None,
+    }
+}
+
+/// Whether the given column is the first one of a pad other than the left-most one
+pub(crate) fn starts_pad(mode: Option<GameMode>, column: u8) -> bool {
+    match mode {
+        Some(mode) if column > 0 => match (mode.pad_of(column - 1), mode.pad_of(column)) {
+            (Some(previous), Some(current)) => previous != current,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub(crate) fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// Returns the measures that start between the one containing `first_row` and `last_row`, as
+/// pairs of measure number and starting row
+///
+/// Measures follow [`Measures`]. Measures that start between rows are moved to the row before.
+/// Rows are split into groups of `rows_per_group`, counted from the first measure, and only the
+/// first measure starting in each group is listed, so the result is never longer than the number
+/// of groups.
+pub(crate) fn measure_starts(
+    tempo_events: &[TempoEvent],
+    first_row: u64,
+    last_row: u64,
+    rows_per_group: u64,
+) -> Vec<(u64, u64)> {
+    let measures = Measures::new(tempo_events);
+    let rows_per_group = rows_per_group.max(1);
+    let mut next_measure = measures
+        .row_to_measure(Row(first_row))
+        .map_or(0, |position| position.measure);
+
+    let mut measure_starts: Vec<(u64, u64)> = Vec::new();
+    loop {
+        let position = MeasurePosition {
+            measure: next_measure,
+            offset: Fraction::from(0),
+        };
+        let row = match measures.measure_to_row(position) {
+            Ok(row) => row.0,
+            Err(BeatError::NotOnGrid { row }) => row.floor(),
+            Err(_) => break,
+        };
+        if row > last_row {
+            break;
+        }
+        measure_starts.push((next_measure, row));
+
+        // Skip the measures that start in the same group as this one
+        let start_row = measure_starts[0].1;
+        let next_group = ((row - start_row) / rows_per_group + 1)
+            .checked_mul(rows_per_group)
+            .and_then(|offset| offset.checked_add(start_row));
+        let next_group = match next_group {
+            Some(next_group) if next_group <= last_row => next_group,
+            _ => break,
+        };
+        next_measure = match measures.row_to_measure(Row(next_group)) {
+            Ok(position) if position.offset == Fraction::from(0) => position.measure,
+            Ok(position) => position.measure + 1,
+            Err(_) => break,
+        };
+    }
+    measure_starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempoEventKind;
+
+    #[test]
+    fn test_snap() {
+        assert_eq!(Snap::from_row(Row(0)), Snap::Fourth);
+        assert_eq!(Snap::from_row(Row(96 + 24)), Snap::Eighth);
+        assert_eq!(Snap::from_row(Row(16)), Snap::Twelfth);
+        assert_eq!(Snap::from_row(Row(36)), Snap::Sixteenth);
+        assert_eq!(Snap::from_row(Row(3)), Snap::SixtyFourth);
+        assert_eq!(Snap::from_row(Row(1)), Snap::HundredNinetySecond);
+    }
+
+    #[test]
+    fn test_measure_starts() {
+        let events = [TempoEvent {
+            row: Row(384),
+            kind: TempoEventKind::TimeSignature {
+                numerator: 3,
+                denominator: 4,
+            },
+        }];
+        assert_eq!(
+            measure_starts(&events, 200, 700, 1),
+            [(1, 192), (2, 384), (3, 528), (4, 672)]
+        );
+
+        // 1/128 measures are a row and a half long
+        let events = [TempoEvent {
+            row: Row(0),
+            kind: TempoEventKind::TimeSignature {
+                numerator: 1,
+                denominator: 128,
+            },
+        }];
+        assert_eq!(
+            measure_starts(&events, 0, 4, 1),
+            [(0, 0), (1, 1), (2, 3), (3, 4)]
+        );
+
+        // Only the first measure of each group is listed
+        assert_eq!(
+            measure_starts(&[], 0, 1000, 400),
+            [(0, 0), (3, 576), (5, 960)]
+        );
+        assert_eq!(measure_starts(&[], 0, u64::MAX, u64::MAX / 4).len(), 4);
+    }
+}
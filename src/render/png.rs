@@ -0,0 +1,368 @@
+//! Renders notes into a PNG image
+//!
+//! The image is rasterized and encoded by this crate itself, so no image libraries are pulled in.
+//! In exchange, shapes are not anti-aliased and the PNG data is stored without compression.
+
+use super::image::{layout, Shape};
+use super::ImageOptions;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::{Note, Row, TempoEvent};
+use alloc::{vec, vec::Vec};
+
+/// Most pixels in an image drawn by [`render`], which needs about a dozen bytes of memory per
+/// pixel while encoding
+pub const MAX_PIXELS: u64 = 1 << 26;
+
+/// Error in [`render`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PngError {
+    /// Image would have more than [`MAX_PIXELS`] pixels, usually because of a large
+    /// [`lane_width`](super::ImageOptions::lane_width)
+    TooLarge {
+        /// Width of the image in pixels
+        width: u32,
+        /// Height of the image in pixels
+        height: u32,
+    },
+}
+
+impl core::fmt::Display for PngError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooLarge { width, height } => write!(
+                f,
+                "image of {}x{} pixels exceeds the limit of {} pixels",
+                width, height, MAX_PIXELS
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PngError {}
+
+/// 3x5 pixel font, covering the characters used in annotations. Every row is stored in the lower
+/// three bits of a byte, most significant bit on the left
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' | 'O' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        _ => [0; 5],
+    }
+}
+
+struct Pixmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Pixmap {
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 3], opacity: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let pixel = &mut self.pixels[y as usize * self.width as usize + x as usize];
+        for (channel, &new) in pixel.iter_mut().zip(color.iter()) {
+            *channel = (*channel as f32 * (1.0 - opacity) + new as f32 * opacity).round() as u8;
+        }
+    }
+
+    /// Blends every pixel whose center lies inside the given bounding box and satisfies `inside`
+    fn fill(
+        &mut self,
+        (min_x, min_y, max_x, max_y): (f32, f32, f32, f32),
+        color: [u8; 3],
+        opacity: f32,
+        inside: impl Fn(f32, f32) -> bool,
+    ) {
+        for y in min_y.floor() as i64..max_y.ceil() as i64 {
+            for x in min_x.floor() as i64..max_x.ceil() as i64 {
+                let (center_x, center_y) = (x as f32 + 0.5, y as f32 + 0.5);
+                if inside(center_x, center_y) {
+                    self.blend(x, y, color, opacity);
+                }
+            }
+        }
+    }
+}
+
+/// Even-odd rule point-in-polygon test
+fn polygon_contains(points: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut inside = false;
+    let mut prev = match points.last() {
+        Some(&point) => point,
+        None => return false,
+    };
+    for &point in points {
+        if (point.1 > y) != (prev.1 > y)
+            && x < (prev.0 - point.0) * (y - point.1) / (prev.1 - point.1) + point.0
+        {
+            inside = !inside;
+        }
+        prev = point;
+    }
+    inside
+}
+
+fn rasterize(
+    notes: &[Note<Row>],
+    tempo_events: &[TempoEvent],
+    options: &ImageOptions,
+) -> Result<Pixmap, PngError> {
+    let canvas = layout(notes, tempo_events, options);
+    if canvas.width as u64 * canvas.height as u64 > MAX_PIXELS {
+        return Err(PngError::TooLarge {
+            width: canvas.width,
+            height: canvas.height,
+        });
+    }
+    let mut pixmap = Pixmap {
+        width: canvas.width,
+        height: canvas.height,
+        pixels: vec![canvas.background; canvas.width as usize * canvas.height as usize],
+    };
+
+    for shape in &canvas.shapes {
+        match shape {
+            &Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+                opacity,
+            } => pixmap.fill((x, y, x + width, y + height), color, opacity, |_, _| true),
+            &Shape::Circle {
+                x,
+                y,
+                radius,
+                color,
+                opacity,
+            } => pixmap.fill(
+                (x - radius, y - radius, x + radius, y + radius),
+                color,
+                opacity,
+                |px, py| (px - x).powi(2) + (py - y).powi(2) <= radius * radius,
+            ),
+            Shape::Polygon {
+                points,
+                color,
+                opacity,
+            } => {
+                let bounds = points.iter().fold(
+                    (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+                    |(min_x, min_y, max_x, max_y), &(x, y)| {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    },
+                );
+                pixmap.fill(bounds, *color, *opacity, |x, y| {
+                    polygon_contains(points, x, y)
+                });
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                color,
+                content,
+            } => {
+                let scale = (size / 5.0).round().max(1.0) as i64;
+                let top = (y - size / 2.0).round() as i64;
+                for (i, c) in content.chars().enumerate() {
+                    let left = x.round() as i64 + i as i64 * 4 * scale;
+                    for (glyph_y, bits) in glyph(c).iter().enumerate() {
+                        for glyph_x in 0..3 {
+                            if bits & (0b100 >> glyph_x) == 0 {
+                                continue;
+                            }
+                            for dy in 0..scale {
+                                for dx in 0..scale {
+                                    pixmap.blend(
+                                        left + glyph_x * scale + dx,
+                                        top + glyph_y as i64 * scale + dy,
+                                        *color,
+                                        1.0,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(pixmap)
+}
+
+fn crc32(data: impl IntoIterator<Item = u8>) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Longest chunk data allowed by the PNG specification
+const MAX_CHUNK_LEN: usize = (1 << 31) - 1;
+
+fn write_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    debug_assert!(data.len() <= MAX_CHUNK_LEN);
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(data);
+    let crc = crc32(chunk_type.iter().chain(data).copied());
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` into a zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK_LEN: usize = 0xFFFF;
+
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK_LEN).peekable();
+    if blocks.peek().is_none() {
+        // Deflate streams need at least one (final) block
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        output.push(is_final as u8);
+        output.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    output
+}
+
+/// Draws `notes` as a vertical chart into a PNG image
+///
+/// The chart looks the same as the one drawn by [`svg::render`](super::svg::render). Returns the
+/// bytes of the PNG file, or [`PngError::TooLarge`] if the image has more than [`MAX_PIXELS`]
+/// pixels.
+///
+/// The pixels are written as stored deflate blocks, without any compression, so the file takes
+/// about three bytes per pixel. Pass it through a PNG optimizer if size matters.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{render::{self, png}, text};
+///
+/// let notes = text::parse_notes("0 1000\n24 0200\n72 M301")?;
+/// let image = png::render(&notes, &[], &render::ImageOptions::default())?;
+/// assert!(image.starts_with(b"\x89PNG\r\n\x1a\n"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn render(
+    notes: &[Note<Row>],
+    tempo_events: &[TempoEvent],
+    options: &ImageOptions,
+) -> Result<Vec<u8>, PngError> {
+    let pixmap = rasterize(notes, tempo_events, options)?;
+
+    // Every scanline is prefixed with its filter type, which is always zero (no filter)
+    let mut scanlines = Vec::with_capacity(pixmap.pixels.len() * 3 + pixmap.height as usize);
+    for row in pixmap.pixels.chunks(pixmap.width.max(1) as usize) {
+        scanlines.push(0);
+        for pixel in row {
+            scanlines.extend_from_slice(pixel);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&pixmap.width.to_be_bytes());
+    header.extend_from_slice(&pixmap.height.to_be_bytes());
+    // Bit depth 8, color type RGB, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut output = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut output, b"IHDR", &header);
+    // The image data may be split across several chunks, which decoders concatenate
+    for data in zlib_stored(&scanlines).chunks(MAX_CHUNK_LEN) {
+        write_chunk(&mut output, b"IDAT", data);
+    }
+    write_chunk(&mut output, b"IEND", &[]);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND".iter().copied()), 0xAE42_6082);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let notes = crate::text::parse_notes("0 0M00").unwrap();
+        let options = ImageOptions {
+            annotations: false,
+            ..Default::default()
+        };
+        let pixmap = rasterize(&notes, &[], &options).unwrap();
+        assert_eq!(
+            (pixmap.width, pixmap.height),
+            (16 + 4 * 48 + 16, 16 + 64 + 16)
+        );
+
+        // Center of the mine in column 1 on row 0
+        let (x, y) = (16 + 48 + 24, 16);
+        assert_eq!(
+            pixmap.pixels[y * pixmap.width as usize + x],
+            [0xE0, 0x1E, 0x1E]
+        );
+    }
+
+    #[test]
+    fn test_too_large() {
+        let notes = crate::text::parse_notes("0 1000").unwrap();
+        let options = ImageOptions {
+            lane_width: 1 << 20,
+            ..Default::default()
+        };
+        assert!(matches!(
+            render(&notes, &[], &options),
+            Err(PngError::TooLarge { .. })
+        ));
+    }
+}
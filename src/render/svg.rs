@@ -0,0 +1,176 @@
+//! Renders notes into an SVG image
+
+use super::image::{layout, Shape};
+use super::ImageOptions;
+use crate::{Note, Row, TempoEvent};
+use alloc::{format, string::String, vec::Vec};
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '&' => escaped += "&amp;",
+            '"' => escaped += "&quot;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Draws `notes` as a vertical chart into an SVG document
+///
+/// Notes are colored by snap, hold and roll bodies are drawn behind them, and thin and thick
+/// horizontal lines mark beats and measures. Measures follow the
+/// [time signatures](crate::TempoEventKind::TimeSignature) in `tempo_events`. BPM changes, stops,
+/// delays and warps from `tempo_events` are annotated beside the lanes, unless disabled in
+/// `options`.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{render::{self, svg}, text, Row, TempoEvent, TempoEventKind};
+///
+/// let notes = text::parse_notes("0 1000\n24 0200\n72 M301")?;
+/// let tempo_events = [TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 150.0 } }];
+///
+/// let image = svg::render(&notes, &tempo_events, &render::ImageOptions::default());
+/// assert!(image.starts_with("<svg"));
+/// assert!(image.contains("150 BPM"));
+/// # Ok::<(), arrowvortex_clipboard::text::TextError>(())
+/// ```
+pub fn render(notes: &[Note<Row>], tempo_events: &[TempoEvent], options: &ImageOptions) -> String {
+    let canvas = layout(notes, tempo_events, options);
+
+    let mut output = format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" "#,
+            r#"width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        ),
+        canvas.width, canvas.height,
+    );
+    output += "\n";
+    output += &format!(
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        hex(canvas.background)
+    );
+    output += "\n";
+
+    for shape in &canvas.shapes {
+        match shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                color,
+                opacity,
+            } => {
+                output += &format!(
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}"/>"#,
+                    x,
+                    y,
+                    width,
+                    height,
+                    hex(*color),
+                    opacity
+                );
+            }
+            Shape::Circle {
+                x,
+                y,
+                radius,
+                color,
+                opacity,
+            } => {
+                output += &format!(
+                    r#"<circle cx="{}" cy="{}" r="{}" fill="{}" fill-opacity="{}"/>"#,
+                    x,
+                    y,
+                    radius,
+                    hex(*color),
+                    opacity
+                );
+            }
+            Shape::Polygon {
+                points,
+                color,
+                opacity,
+            } => {
+                let points = points
+                    .iter()
+                    .map(|(x, y)| format!("{},{}", x, y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                output += &format!(
+                    r#"<polygon points="{}" fill="{}" fill-opacity="{}"/>"#,
+                    points,
+                    hex(*color),
+                    opacity
+                );
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                color,
+                content,
+            } => {
+                output += &format!(
+                    concat!(
+                        r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" "#,
+                        r#"dominant-baseline="middle" fill="{}">{}</text>"#,
+                    ),
+                    x,
+                    y,
+                    size,
+                    hex(*color),
+                    escape_xml(content)
+                );
+            }
+        }
+        output += "\n";
+    }
+
+    output += "</svg>\n";
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let notes = crate::text::parse_notes("0 20F0\n48 3001").unwrap();
+        let image = render(&notes, &[], &Default::default());
+
+        // Background, four lanes, three beat lines, one measure line and one hold body
+        assert_eq!(image.matches("<rect").count(), 1 + 4 + 3 + 1 + 1);
+        assert_eq!(image.matches("<polygon").count(), 3);
+        assert!(image.contains(r#"fill-opacity="0.35""#));
+        assert!(image.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_limits() {
+        let note = |pos| Note {
+            pos: Row(pos),
+            column: 0,
+            kind: crate::NoteKind::Tap,
+        };
+        let options = ImageOptions {
+            annotations: false,
+            ..Default::default()
+        };
+        for &last_row in &[10_000_000, Row::MAX.0, u64::MAX] {
+            let canvas = layout(&[note(0), note(last_row)], &[], &options);
+            assert!(canvas.height <= crate::render::MAX_IMAGE_HEIGHT);
+            // Beat lines are dropped and measure lines thinned out to one per few pixels
+            assert!(canvas.shapes.len() < 10_000);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Renders notes as a vertical chart of text lines, to be printed into a terminal
+
+use super::{gcd, lane, measure_starts, starts_pad, Snap};
+use crate::mode::{GameMode, Lane};
+use crate::{Note, NoteKind, Row, TempoEvent, ROWS_PER_BEAT};
+use alloc::{borrow::ToOwned, format, string::String, vec};
+
+/// Settings for [`render`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalOptions {
+    /// Use Unicode arrows and box drawing characters instead of plain ASCII
+    pub unicode: bool,
+    /// Color notes by snap using ANSI escape sequences
+    pub colors: bool,
+    /// Number of rows that each line stands for. If `None`, the coarsest resolution that shows
+    /// every note on its own line is chosen, as long as that needs no more than
+    /// [`MAX_AUTO_LINES`] lines
+    pub rows_per_line: Option<u64>,
+    /// Minimum number of columns to draw
+    pub num_columns: u8,
+    /// Game mode whose lanes to draw, with an extra space between pads. If `None`, four and eight
+    /// columns are drawn as dance arrows and everything else as generic note heads
+    pub mode: Option<GameMode>,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> Self {
+        Self {
+            unicode: true,
+            colors: true,
+            rows_per_line: None,
+            num_columns: 4,
+            mode: None,
+        }
+    }
+}
+
+/// Most lines drawn when [`TerminalOptions::rows_per_line`] is `None`. Sparse notes on odd rows
+/// would otherwise need one line per row; past this limit, notes share lines instead
+pub const MAX_AUTO_LINES: u64 = 4096;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+
+fn ansi_color([r, g, b]: [u8; 3]) -> String {
+    // Map onto the 6x6x6 color cube of 256-color terminals, which is more widely supported than
+    // true color
+    let channel = |c: u8| (c as u32 * 5 + 127) / 255;
+    format!(
+        "\x1b[38;5;{}m",
+        16 + 36 * channel(r) + 6 * channel(g) + channel(b)
+    )
+}
+
+fn arrow_glyph(lane: Option<Lane>, unicode: bool) -> char {
+    let eighth_turns = lane.and_then(Lane::eighth_turns);
+    match (lane, eighth_turns) {
+        (_, Some(eighth_turns)) => {
+            let unicode_arrows = ['↑', '↗', '→', '↘', '↓', '↙', '←', '↖'];
+            let ascii_arrows = ['^', '/', '>', '\\', 'v', '/', '<', '\\'];
+            let arrows = if unicode {
+                unicode_arrows
+            } else {
+                ascii_arrows
+            };
+            arrows[eighth_turns as usize]
+        }
+        (Some(Lane::Key(key)), None) => core::char::from_digit(key as u32, 10).unwrap_or('o'),
+        _ if unicode => '●',
+        _ => 'o',
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Empty,
+    HoldBody,
+    RollBody,
+    Tail,
+    Head(Row, NoteKindTag),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NoteKindTag {
+    Tap,
+    Hold,
+    Roll,
+    Mine,
+    Lift,
+    Fake,
+}
+
+impl Cell {
+    fn priority(self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::HoldBody | Cell::RollBody => 1,
+            Cell::Tail => 2,
+            Cell::Head(..) => 3,
+        }
+    }
+}
+
+fn draw_cell(output: &mut String, cell: Cell, lane: Option<Lane>, options: &TerminalOptions) {
+    let unicode = options.unicode;
+    let (glyph, color) = match cell {
+        Cell::Empty => (if unicode { '·' } else { '.' }, None),
+        Cell::HoldBody => (
+            if unicode { '║' } else { '|' },
+            Some(ansi_color([0x32, 0xC8, 0x32])),
+        ),
+        Cell::RollBody => (
+            if unicode { '┊' } else { ':' },
+            Some(ansi_color([0xF0, 0xDC, 0x1E])),
+        ),
+        Cell::Tail => (if unicode { '╨' } else { '_' }, None),
+        Cell::Head(row, kind) => {
+            let snap_color = ansi_color(Snap::from_row(row).color());
+            match kind {
+                NoteKindTag::Tap | NoteKindTag::Hold | NoteKindTag::Roll => {
+                    (arrow_glyph(lane, unicode), Some(snap_color))
+                }
+                NoteKindTag::Mine => (
+                    if unicode { '✱' } else { '*' },
+                    Some(ansi_color([0x96, 0x96, 0x96])),
+                ),
+                NoteKindTag::Lift => ('L', Some(snap_color)),
+                NoteKindTag::Fake if options.colors => {
+                    (arrow_glyph(lane, unicode), Some(DIM.to_owned()))
+                }
+                NoteKindTag::Fake => ('F', None),
+            }
+        }
+    };
+
+    match color {
+        Some(color) if options.colors => {
+            output.push_str(&color);
+            output.push(glyph);
+            output.push_str(RESET);
+        }
+        _ => output.push(glyph),
+    }
+}
+
+/// Draws `notes` as a vertical chart, scrolling downwards, with one line per row group
+///
+/// Every line starts with its row number. Measure lines are derived from the
+/// [time signatures](crate::TempoEventKind::TimeSignature) in `tempo_events`; measures are 4/4
+/// until the first time signature.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{render::terminal, text};
+///
+/// let notes = text::parse_notes("0 1000\n24 0200\n72 M301")?;
+/// let options = terminal::TerminalOptions { colors: false, ..Default::default() };
+///
+/// assert_eq!(terminal::render(&notes, &[], &options), "\
+/// ────── measure 1
+///      0 ← · · ·
+///     24 · ↓ · ·
+///     48 · ║ · ·
+///     72 ✱ ╨ · →
+/// ");
+/// # Ok::<(), arrowvortex_clipboard::text::TextError>(())
+/// ```
+pub fn render(
+    notes: &[Note<Row>],
+    tempo_events: &[TempoEvent],
+    options: &TerminalOptions,
+) -> String {
+    let num_columns = notes
+        .iter()
+        .map(|note| note.column as usize + 1)
+        .max()
+        .unwrap_or(0)
+        .max(options.num_columns as usize)
+        .max(options.mode.map_or(0, GameMode::num_columns) as usize);
+    let end_pos = |note: &Note<Row>| match note.kind {
+        NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => end_pos.max(note.pos).0,
+        _ => note.pos.0,
+    };
+    let (first_row, last_row) = match (
+        notes.iter().map(|note| note.pos.0).min(),
+        notes.iter().map(end_pos).max(),
+    ) {
+        (Some(first_row), Some(last_row)) => (first_row, last_row),
+        _ => return String::new(),
+    };
+
+    // In auto mode, at most MAX_AUTO_LINES lines are drawn, so more measures than that can't
+    // get a rule each anyway
+    let min_rows_per_line = match options.rows_per_line {
+        Some(rows_per_line) => rows_per_line,
+        None => (last_row - first_row) / MAX_AUTO_LINES + 1,
+    };
+    let measures = measure_starts(tempo_events, first_row, last_row, min_rows_per_line);
+    let start_row = measures.first().map_or(first_row, |&(_, row)| row);
+    let rows_per_line = options
+        .rows_per_line
+        .unwrap_or_else(|| {
+            notes
+                .iter()
+                .flat_map(|note| {
+                    core::iter::once(note.pos.0).chain(core::iter::once(end_pos(note)))
+                })
+                .chain(measures.iter().map(|&(_, row)| row))
+                .fold(ROWS_PER_BEAT, |acc, row| gcd(acc, row - start_row))
+                .max((last_row - start_row) / MAX_AUTO_LINES + 1)
+        })
+        .max(1);
+    // Leaves at most one measure rule per line
+    let measures = measure_starts(tempo_events, first_row, last_row, rows_per_line);
+
+    let line_of = |row: u64| ((row - start_row) / rows_per_line) as usize;
+    let num_lines = line_of(last_row) + 1;
+    let mut grid = vec![Cell::Empty; num_lines * num_columns];
+    let mut set_cell = |line: usize, column: u8, cell: Cell| {
+        let slot = &mut grid[line * num_columns + column as usize];
+        if cell.priority() >= slot.priority() {
+            *slot = cell;
+        }
+    };
+    for note in notes {
+        let (tag, body) = match note.kind {
+            NoteKind::Tap => (NoteKindTag::Tap, None),
+            NoteKind::Hold { .. } => (NoteKindTag::Hold, Some(Cell::HoldBody)),
+            NoteKind::Mine => (NoteKindTag::Mine, None),
+            NoteKind::Roll { .. } => (NoteKindTag::Roll, Some(Cell::RollBody)),
+            NoteKind::Lift => (NoteKindTag::Lift, None),
+            NoteKind::Fake => (NoteKindTag::Fake, None),
+        };
+        let (head_line, tail_line) = (line_of(note.pos.0), line_of(end_pos(note)));
+        set_cell(head_line, note.column, Cell::Head(note.pos, tag));
+        if let Some(body) = body {
+            for line in head_line + 1..tail_line {
+                set_cell(line, note.column, body);
+            }
+            if tail_line > head_line {
+                set_cell(tail_line, note.column, Cell::Tail);
+            }
+        }
+    }
+
+    let mut output = String::new();
+    let mut measures = measures.iter().peekable();
+    for (line, cells) in grid.chunks(num_columns).enumerate() {
+        let row = start_row + line as u64 * rows_per_line;
+        let next_row = row.saturating_add(rows_per_line);
+        if let Some((measure, _)) =
+            measures.next_if(|&&(_, measure_start)| measure_start < next_row)
+        {
+            let rule = if options.unicode {
+                "──────"
+            } else {
+                "------"
+            };
+            output += &format!("{} measure {}\n", rule, measure + 1);
+        }
+
+        output += &format!("{:>6}", row);
+        for (column, &cell) in cells.iter().enumerate() {
+            let column = column as u8;
+            if starts_pad(options.mode, column) {
+                output.push(' ');
+            }
+            output.push(' ');
+            let lane = lane(options.mode, column, num_columns);
+            draw_cell(&mut output, cell, lane, options);
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempoEventKind;
+
+    #[test]
+    fn test_render() {
+        let notes = crate::text::parse_notes("0 4000\n144 30F0\n192 0L00").unwrap();
+        let tempo_events = [TempoEvent {
+            row: Row(0),
+            kind: TempoEventKind::TimeSignature {
+                numerator: 3,
+                denominator: 4,
+            },
+        }];
+
+        let options = TerminalOptions {
+            unicode: false,
+            colors: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            render(&notes, &tempo_events, &options),
+            "------ measure 1\n     0 < . . .\n    48 : . . .\n    96 : . . .\n\
+             ------ measure 2\n   144 _ . F .\n   192 . L . .\n",
+        );
+
+        let colored = render(&notes, &tempo_events, &Default::default());
+        let first_line = "────── measure 1\n     0 \x1b[38;5;167m←\x1b[0m";
+        assert!(colored.starts_with(first_line));
+    }
+
+    #[test]
+    fn test_modes() {
+        let render_mode = |text: &str, mode| {
+            let notes = crate::text::parse_notes(text).unwrap();
+            let options = TerminalOptions {
+                colors: false,
+                mode: Some(mode),
+                ..Default::default()
+            };
+            render(&notes, &[], &options)
+        };
+        assert_eq!(
+            render_mode("0 10000001", GameMode::DanceDouble),
+            "────── measure 1\n     0 ← · · ·  · · · →\n",
+        );
+        assert_eq!(
+            render_mode("0 11111", GameMode::PumpSingle),
+            "────── measure 1\n     0 ↙ ↖ ● ↗ ↘\n",
+        );
+        assert_eq!(
+            render_mode("0 0000001", GameMode::Kb7Single),
+            "────── measure 1\n     0 · · · · · · 7\n",
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let note = |pos, column| Note {
+            pos: Row(pos),
+            column,
+            kind: NoteKind::Tap,
+        };
+        let notes = [note(0, 255), note(1, 0), note(10_000_000, 0)];
+        let options = TerminalOptions {
+            unicode: false,
+            colors: false,
+            ..Default::default()
+        };
+        let output = render(&notes, &[], &options);
+        // Every line but the last one, which starts after the last measure, gets a measure rule
+        assert_eq!(output.lines().count() as u64, 2 * MAX_AUTO_LINES - 1);
+        let note_lines = output.lines().filter(|line| !line.starts_with('-'));
+        assert_eq!(note_lines.clone().count() as u64, MAX_AUTO_LINES);
+        assert!(note_lines
+            .clone()
+            .all(|line| line.trim_start().split(' ').count() == 1 + 256));
+
+        // Measures are far shorter than a line here
+        let notes = [note(0, 0), note(Row::MAX.0, 0), note(u64::MAX, 0)];
+        let output = render(&notes, &[], &options);
+        assert_eq!(output.lines().count() as u64, 2 * MAX_AUTO_LINES);
+    }
+}
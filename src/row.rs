@@ -0,0 +1,115 @@
+use crate::beat::Fraction;
+use crate::ROWS_PER_BEAT;
+
+/// Position on the grid of [`ROWS_PER_BEAT`] rows per beat, shared by notes and tempo events
+///
+/// Rows are stored as `u64`, but ArrowVortex itself uses 32-bit rows: encoding fails with
+/// [`EncodeError::RowOutOfRange`](crate::EncodeError::RowOutOfRange) beyond [`Row::MAX`].
+///
+/// ```rust
+/// use arrowvortex_clipboard::Row;
+///
+/// let row = Row::from_beat(3) + 12;
+/// assert_eq!(row, Row(156));
+/// assert_eq!(row.to_string(), "beat 3 + 1/4");
+/// assert_eq!(Row(96) - Row(48), 48);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Row(pub u64);
+
+impl Row {
+    /// The first row
+    pub const ZERO: Self = Self(0);
+
+    /// The last row that ArrowVortex supports
+    pub const MAX: Self = Self(u32::MAX as u64);
+
+    /// Returns the row at the start of the given beat
+    pub fn from_beat(beat: u64) -> Self {
+        Self(beat * ROWS_PER_BEAT)
+    }
+
+    /// Returns the beat this row is in, rounded down
+    pub fn beat(self) -> u64 {
+        self.0 / ROWS_PER_BEAT
+    }
+
+    /// Returns the row as a 32-bit integer, or `None` if it is beyond [`Row::MAX`]
+    pub fn to_u32(self) -> Option<u32> {
+        if self <= Self::MAX {
+            Some(self.0 as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Adds a number of rows, returning `None` on overflow
+    pub fn checked_add(self, rows: u64) -> Option<Self> {
+        self.0.checked_add(rows).map(Self)
+    }
+
+    /// Adds a number of rows, stopping at [`Row::MAX`]. Rows that are already beyond it are
+    /// returned unchanged
+    pub fn saturating_add(self, rows: u64) -> Self {
+        Self(self.0.saturating_add(rows).min(Self::MAX.0.max(self.0)))
+    }
+
+    /// Returns the number of rows from `earlier` to this row, or `None` if `earlier` is later
+    pub fn checked_sub(self, earlier: Self) -> Option<u64> {
+        self.0.checked_sub(earlier.0)
+    }
+}
+
+impl From<u32> for Row {
+    fn from(row: u32) -> Self {
+        Self(row as u64)
+    }
+}
+
+impl From<Row> for u64 {
+    fn from(row: Row) -> Self {
+        row.0
+    }
+}
+
+impl core::ops::Add<u64> for Row {
+    type Output = Self;
+
+    /// Panics on overflow in debug builds, like integer addition. See [`Row::checked_add`] and
+    /// [`Row::saturating_add`] for alternatives
+    fn add(self, rows: u64) -> Self {
+        Self(self.0 + rows)
+    }
+}
+
+impl core::ops::AddAssign<u64> for Row {
+    fn add_assign(&mut self, rows: u64) {
+        self.0 += rows;
+    }
+}
+
+impl core::ops::Sub for Row {
+    type Output = u64;
+
+    /// Number of rows between two rows
+    ///
+    /// # Panics
+    ///
+    /// Panics if `earlier` is after `self`. See [`Row::checked_sub`] for an alternative
+    fn sub(self, earlier: Self) -> u64 {
+        self.0 - earlier.0
+    }
+}
+
+impl core::fmt::Display for Row {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "beat {}", self.beat())?;
+        let remainder = self.0 % ROWS_PER_BEAT;
+        if remainder != 0 {
+            let fraction =
+                Fraction::new(remainder, ROWS_PER_BEAT).expect("denominator is not zero");
+            write!(f, " + {}", fraction)?;
+        }
+        Ok(())
+    }
+}
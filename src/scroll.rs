@@ -0,0 +1,261 @@
+/*!
+Visual scroll positions of rows, taking [Speed](crate::TempoEventKind::Speed) and
+[Scroll](crate::TempoEventKind::Scroll) changes into account
+
+Both tempo events change the spacing between notes on screen, but in different ways:
+
+- A scroll change affects the rows that follow it, until the next scroll change. With a ratio of
+  0.5, the rows in that range are drawn half as far apart as usual. The spacing on screen is fixed,
+  no matter where in the song the player currently is.
+- A speed change affects the entire screen while it is active, like changing the player's speed
+  mod. The multiplier changes gradually, from the ratio of the previous speed change (or 1) to the
+  new ratio, over the transition length given by `delay`. The transition starts when the song
+  reaches the speed change's row and lasts `delay` beats, or `delay` seconds if `delay_is_time`
+  is set.
+
+```rust
+use arrowvortex_clipboard::{scroll::ScrollModel, Row, TempoEvent, TempoEventKind};
+
+let model = ScrollModel::new(&[
+    TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 60.0 } },
+    TempoEvent {
+        row: Row(0),
+        kind: TempoEventKind::Speed { ratio: 2.0, delay: 1.0, delay_is_time: false },
+    },
+    TempoEvent { row: Row(192), kind: TempoEventKind::Scroll { ratio: 0.5 } },
+]);
+
+// Halfway through the one-beat transition from 1x to 2x
+assert_eq!(model.speed_at(0.5), 1.5);
+// Four beats at normal scroll speed, then two beats at half scroll speed
+assert_eq!(model.scroll_position(288.0), 5.0);
+// At 2x speed, the row is drawn twice as far away from the receptors
+assert_eq!(model.note_position(Row(288), 1.0), 2.0 * (5.0 - 1.0));
+```
+*/
+
+use crate::timing::Timing;
+use crate::{Row, TempoEvent, TempoEventKind, ROWS_PER_BEAT};
+use alloc::{vec, vec::Vec};
+
+#[derive(Debug, Clone, PartialEq)]
+struct ScrollSegment {
+    row: u64,
+    /// Scroll position at `row`, in beats
+    position: f64,
+    ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpeedChange {
+    row: u64,
+    /// Time at which the transition starts
+    time: f64,
+    ratio: f64,
+    delay: f64,
+    delay_is_time: bool,
+}
+
+/// Computes where rows are drawn on screen, relative to the receptors, at any point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollModel {
+    timing: Timing,
+    scroll_segments: Vec<ScrollSegment>,
+    speed_changes: Vec<SpeedChange>,
+}
+
+impl ScrollModel {
+    /// Builds the model from a list of tempo events, in any order
+    ///
+    /// Besides speed and scroll changes, the BPM changes, stops, delays and warps are used to find
+    /// out which row the song is at at any given time. If there are several speed or scroll changes
+    /// on one row, the last one takes precedence.
+    pub fn new(tempo_events: &[TempoEvent]) -> Self {
+        let timing = Timing::new(tempo_events);
+
+        let mut scroll_changes = Vec::new();
+        let mut speed_changes = Vec::new();
+        for event in tempo_events {
+            let row = event.row.0;
+            match event.kind {
+                TempoEventKind::Scroll { ratio } => scroll_changes.push((row, ratio)),
+                TempoEventKind::Speed {
+                    ratio,
+                    delay,
+                    delay_is_time,
+                } => speed_changes.push(SpeedChange {
+                    row,
+                    time: timing.row_to_time(event.row),
+                    ratio,
+                    delay,
+                    delay_is_time,
+                }),
+                _ => {}
+            }
+        }
+        scroll_changes.sort_by_key(|&(row, _)| row);
+        speed_changes.sort_by_key(|change| change.row);
+
+        let mut scroll_segments = vec![ScrollSegment {
+            row: 0,
+            position: 0.0,
+            ratio: 1.0,
+        }];
+        for (row, ratio) in scroll_changes {
+            let last = scroll_segments.last_mut().unwrap();
+            if row == last.row {
+                last.ratio = ratio;
+                continue;
+            }
+            let position =
+                last.position + (row - last.row) as f64 * last.ratio / ROWS_PER_BEAT as f64;
+            scroll_segments.push(ScrollSegment {
+                row,
+                position,
+                ratio,
+            });
+        }
+        speed_changes.dedup_by(|later, earlier| {
+            let is_same_row = later.row == earlier.row;
+            if is_same_row {
+                *earlier = later.clone();
+            }
+            is_same_row
+        });
+
+        Self {
+            timing,
+            scroll_segments,
+            speed_changes,
+        }
+    }
+
+    /// Returns the timing that is used to find the current row
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    /// Returns the speed multiplier at the given time in seconds
+    pub fn speed_at(&self, time: f64) -> f64 {
+        let index = match self
+            .speed_changes
+            .iter()
+            .rposition(|change| change.time <= time)
+        {
+            Some(index) => index,
+            None => return 1.0,
+        };
+        let change = &self.speed_changes[index];
+        let previous_ratio = match index {
+            0 => 1.0,
+            _ => self.speed_changes[index - 1].ratio,
+        };
+
+        let progress = if change.delay <= 0.0 {
+            1.0
+        } else if change.delay_is_time {
+            (time - change.time) / change.delay
+        } else {
+            let beats = (self.timing.time_to_row(time) - change.row as f64) / ROWS_PER_BEAT as f64;
+            beats / change.delay
+        };
+        previous_ratio + (change.ratio - previous_ratio) * progress.clamp(0.0, 1.0)
+    }
+
+    /// Returns the scroll position of the given (possibly fractional) row, in beats
+    ///
+    /// Without scroll changes, this is simply the beat of the row. The distance between two rows
+    /// on screen is proportional to the difference of their scroll positions.
+    pub fn scroll_position(&self, row: f64) -> f64 {
+        let segment = self
+            .scroll_segments
+            .iter()
+            .rev()
+            .find(|segment| segment.row as f64 <= row)
+            .unwrap_or(&self.scroll_segments[0]);
+        segment.position + (row - segment.row as f64) * segment.ratio / ROWS_PER_BEAT as f64
+    }
+
+    /// Returns how far below the receptors the given row is drawn at the given time in seconds
+    ///
+    /// The distance is measured in beats at 1x speed, so it has to be multiplied with the distance
+    /// between two beats on screen. It is negative for rows that have already passed the receptors.
+    pub fn note_position(&self, row: Row, time: f64) -> f64 {
+        let current_position = self.scroll_position(self.timing.time_to_row(time));
+        self.speed_at(time) * (self.scroll_position(row.0 as f64) - current_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speed(row: u32, ratio: f64, delay: f64, delay_is_time: bool) -> TempoEvent {
+        TempoEvent {
+            row: Row::from(row),
+            kind: TempoEventKind::Speed {
+                ratio,
+                delay,
+                delay_is_time,
+            },
+        }
+    }
+
+    #[test]
+    fn test_speed_transitions() {
+        let model = ScrollModel::new(&[
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 120.0 },
+            },
+            // Two beats (one second) from 1x to 3x, starting at beat 1
+            speed(48, 3.0, 2.0, false),
+            // Immediately to 0.5x at beat 8
+            speed(384, 0.5, 0.0, false),
+            // One second from 3x to 1x at beat 4
+            speed(192, 1.0, 1.0, true),
+            TempoEvent {
+                row: Row(192),
+                kind: TempoEventKind::Stop { time: 1.0 },
+            },
+        ]);
+
+        assert_eq!(model.speed_at(0.0), 1.0);
+        assert_eq!(model.speed_at(0.5), 1.0);
+        assert_eq!(model.speed_at(1.0), 2.0);
+        assert_eq!(model.speed_at(1.5), 3.0);
+        // Time-based transitions continue during the stop
+        assert_eq!(model.speed_at(2.5), 2.0);
+        assert_eq!(model.speed_at(3.0), 1.0);
+        assert_eq!(model.speed_at(5.0), 0.5);
+    }
+
+    #[test]
+    fn test_scroll_positions() {
+        let model = ScrollModel::new(&[
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Scroll { ratio: 0.0 },
+            },
+            TempoEvent {
+                row: Row(144),
+                kind: TempoEventKind::Scroll { ratio: -1.0 },
+            },
+            TempoEvent {
+                row: Row(192),
+                kind: TempoEventKind::Scroll { ratio: 2.0 },
+            },
+        ]);
+
+        assert_eq!(model.scroll_position(48.0), 1.0);
+        assert_eq!(model.scroll_position(120.0), 2.0);
+        assert_eq!(model.scroll_position(192.0), 1.0);
+        assert_eq!(model.scroll_position(216.0), 2.0);
+        // At 120 BPM, row 96 is reached after one second; from then on row 120 stays in place
+        assert_eq!(model.note_position(Row(120), 0.0), 2.0);
+        assert_eq!(model.note_position(Row(120), 1.0), 0.0);
+        assert_eq!(model.note_position(Row(120), 1.25), 0.0);
+        // Rows after the reverse scroll section come back towards the receptors
+        assert_eq!(model.note_position(Row(216), 1.25), 0.0);
+    }
+}
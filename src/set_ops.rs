@@ -0,0 +1,607 @@
+/*!
+Set operations on note selections and tempo events, for combining and comparing copies of the same
+chart
+
+Notes are identified by their position and column. Two notes at the same spot but of different
+kind, like a tap and a hold, are in conflict. If one selection has several notes at the same spot,
+only the last of them counts. All note functions accept notes in any order and
+return them sorted by position and column, as
+[`encode_row_based_notes`](crate::encode_row_based_notes) expects.
+
+Tempo events are identified by their type and row, see [`diff_tempo`] and [`merge_tempo`].
+
+```rust
+use arrowvortex_clipboard::{set_ops, text};
+
+let ours = text::parse_notes("0 1000\n12 0100\n24 0010")?;
+let theirs = text::parse_notes("0 1000\n12 0200\n36 0301")?;
+
+let diff = set_ops::diff(&ours, &theirs);
+assert_eq!(diff.added, text::parse_notes("36 0001")?);
+assert_eq!(diff.removed, text::parse_notes("24 0010")?);
+assert_eq!(diff.changed.len(), 1);
+assert_eq!(diff.changed[0].new, theirs[1]);
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::encode::tempo_event_kind;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::{Note, NoteKind, Row, TempoEvent, TempoEventKind};
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, vec::Vec};
+
+/// What [`union`] and [`merge_tempo`] do when both inputs have different entries at the same spot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictPolicy {
+    /// Keep the note from the first selection
+    KeepFirst,
+    /// Keep the note from the second selection
+    KeepSecond,
+    /// Fail with a [`NoteConflict`]
+    Reject,
+}
+
+/// Two different notes at the same position and column, or a note inside or on the tail of a hold
+/// or roll from the other selection
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NoteConflict<P> {
+    /// Note from the first selection
+    pub first: Note<P>,
+    /// Note from the second selection
+    pub second: Note<P>,
+}
+
+impl<P: core::fmt::Debug> core::fmt::Display for NoteConflict<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "conflicting notes at position {:?}, column {}: {:?} and {:?}",
+            self.first.pos, self.first.column, self.first.kind, self.second.kind
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P: core::fmt::Debug> std::error::Error for NoteConflict<P> {}
+
+/// A note that exists in both selections, but differs in kind or hold length
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NoteChange<P> {
+    /// Note in the old selection
+    pub old: Note<P>,
+    /// Note in the new selection
+    pub new: Note<P>,
+}
+
+/// Result of [`diff`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NoteDiff<P> {
+    /// Notes that only exist in the new selection
+    pub added: Vec<Note<P>>,
+    /// Notes that only exist in the old selection
+    pub removed: Vec<Note<P>>,
+    /// Notes that exist in both selections with different kind or hold length
+    pub changed: Vec<NoteChange<P>>,
+}
+
+impl<P> NoteDiff<P> {
+    /// Returns true if both selections were identical
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Later notes on the same spot replace earlier ones
+fn by_spot<P: Ord + Clone>(notes: &[Note<P>]) -> BTreeMap<(P, u8), &Note<P>> {
+    notes
+        .iter()
+        .map(|note| ((note.pos.clone(), note.column), note))
+        .collect()
+}
+
+/// Combines both selections
+///
+/// Identical notes appear only once. Different notes at the same spot are resolved by `policy`, and
+/// so are notes inside or on the tail of a hold or roll from the other selection; the losing note
+/// or hold is left out.
+pub fn union<P: Ord + Clone>(
+    first: &[Note<P>],
+    second: &[Note<P>],
+    policy: ConflictPolicy,
+) -> Result<Vec<Note<P>>, NoteConflict<P>> {
+    // Every note is tagged with whether it comes from the second selection
+    let mut notes = by_spot(first)
+        .into_iter()
+        .map(|(spot, note)| (spot, (note, false)))
+        .collect::<BTreeMap<_, _>>();
+    for (spot, note) in by_spot(second) {
+        match notes.get(&spot) {
+            Some(&(existing, _)) if existing != note => match policy {
+                ConflictPolicy::KeepFirst => {}
+                ConflictPolicy::KeepSecond => {
+                    notes.insert(spot, (note, true));
+                }
+                ConflictPolicy::Reject => {
+                    return Err(NoteConflict {
+                        first: existing.clone(),
+                        second: note.clone(),
+                    })
+                }
+            },
+            Some(_) => {}
+            None => {
+                notes.insert(spot, (note, true));
+            }
+        }
+    }
+
+    let end_pos = |note: &Note<P>| match &note.kind {
+        NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => Some(end_pos.clone()),
+        _ => None,
+    };
+    let mut by_column = notes.values().copied().collect::<Vec<_>>();
+    by_column.sort_by(|(a, _), (b, _)| (a.column, &a.pos).cmp(&(b.column, &b.pos)));
+    let mut dropped = Vec::new();
+    // Latest hold or roll in the current column, with its end and origin
+    let mut active_hold: Option<(&Note<P>, P, bool)> = None;
+    for (note, from_second) in by_column {
+        if let Some((hold, hold_end, hold_from_second)) = active_hold.clone() {
+            let overlaps = hold.column == note.column && note.pos <= hold_end;
+            if overlaps && hold_from_second != from_second {
+                let (first_note, second_note) = if from_second {
+                    (hold, note)
+                } else {
+                    (note, hold)
+                };
+                let drop_note = match policy {
+                    ConflictPolicy::KeepFirst => from_second,
+                    ConflictPolicy::KeepSecond => !from_second,
+                    ConflictPolicy::Reject => {
+                        return Err(NoteConflict {
+                            first: first_note.clone(),
+                            second: second_note.clone(),
+                        })
+                    }
+                };
+                if drop_note {
+                    dropped.push(note);
+                    continue;
+                }
+                dropped.push(hold);
+                active_hold = None;
+            }
+        }
+        if let Some(note_end) = end_pos(note) {
+            active_hold = Some((note, note_end, from_second));
+        }
+    }
+    for note in dropped {
+        notes.remove(&(note.pos.clone(), note.column));
+    }
+
+    Ok(notes
+        .into_iter()
+        .map(|(_, (note, _))| note.clone())
+        .collect())
+}
+
+/// Returns the notes that are identical in both selections
+pub fn intersection<P: Ord + Clone>(first: &[Note<P>], second: &[Note<P>]) -> Vec<Note<P>> {
+    let second = by_spot(second);
+    let mut notes = first
+        .iter()
+        .filter(|note| second.get(&(note.pos.clone(), note.column)) == Some(note))
+        .cloned()
+        .collect::<Vec<_>>();
+    notes.sort();
+    notes.dedup();
+    notes
+}
+
+/// Returns the notes of `first` that have no identical counterpart in `second`
+///
+/// Together with [`intersection`], this splits `first` into the notes both selections agree on
+/// and the rest.
+pub fn difference<P: Ord + Clone>(first: &[Note<P>], second: &[Note<P>]) -> Vec<Note<P>> {
+    let second = by_spot(second);
+    let mut notes = first
+        .iter()
+        .filter(|note| second.get(&(note.pos.clone(), note.column)) != Some(note))
+        .cloned()
+        .collect::<Vec<_>>();
+    notes.sort();
+    notes.dedup();
+    notes
+}
+
+/// Compares two versions of a selection
+pub fn diff<P: Ord + Clone>(old: &[Note<P>], new: &[Note<P>]) -> NoteDiff<P> {
+    let (old, new) = (by_spot(old), by_spot(new));
+    let mut diff = NoteDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+    for (spot, &old_note) in &old {
+        match new.get(spot) {
+            None => diff.removed.push(old_note.clone()),
+            Some(&new_note) if new_note != old_note => diff.changed.push(NoteChange {
+                old: old_note.clone(),
+                new: new_note.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (spot, &new_note) in &new {
+        if !old.contains_key(spot) {
+            diff.added.push(new_note.clone());
+        }
+    }
+    diff
+}
+
+/// Two tempo events of the same type on the same row whose values differ by more than the
+/// tolerance
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoConflict {
+    /// Event from the first list
+    pub first: TempoEvent,
+    /// Event from the second list
+    pub second: TempoEvent,
+}
+
+impl core::fmt::Display for TempoConflict {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "conflicting tempo events at row {}: {} and {}",
+            self.first.row.0,
+            describe(&self.first.kind),
+            describe(&self.second.kind)
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TempoConflict {}
+
+/// A tempo event that exists in both lists, but with different values
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoChange {
+    /// Event in the old list
+    pub old: TempoEvent,
+    /// Event in the new list
+    pub new: TempoEvent,
+}
+
+/// Result of [`diff_tempo`]
+///
+/// The [`Display`](core::fmt::Display) implementation prints a human-readable report with one
+/// line per difference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoDiff {
+    /// Events that only exist in the new list
+    pub added: Vec<TempoEvent>,
+    /// Events that only exist in the old list
+    pub removed: Vec<TempoEvent>,
+    /// Events that exist in both lists with different values
+    pub changed: Vec<TempoChange>,
+}
+
+impl TempoDiff {
+    /// Returns true if both lists matched within the tolerance
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl core::fmt::Display for TempoDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Sorted by row rather than by type, which is easier to follow for humans
+        let mut lines = Vec::new();
+        for event in &self.added {
+            lines.push((
+                event.row,
+                format!("+ row {}: {}", event.row.0, describe(&event.kind)),
+            ));
+        }
+        for event in &self.removed {
+            lines.push((
+                event.row,
+                format!("- row {}: {}", event.row.0, describe(&event.kind)),
+            ));
+        }
+        for change in &self.changed {
+            lines.push((
+                change.old.row,
+                format!(
+                    "~ row {}: {} -> {}",
+                    change.old.row.0,
+                    describe(&change.old.kind),
+                    describe(&change.new.kind)
+                ),
+            ));
+        }
+        lines.sort_by_key(|&(row, _)| row);
+
+        for (_, line) in lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(kind: &TempoEventKind) -> String {
+    match kind {
+        TempoEventKind::Bpm { bpm } => format!("BPM {}", bpm),
+        TempoEventKind::Stop { time } => format!("stop {}s", time),
+        TempoEventKind::Delay { time } => format!("delay {}s", time),
+        TempoEventKind::Warp { num_skipped_rows } => format!("warp {} rows", num_skipped_rows),
+        TempoEventKind::TimeSignature {
+            numerator,
+            denominator,
+        } => format!("time signature {}/{}", numerator, denominator),
+        TempoEventKind::Ticks { num_ticks } => format!("{} ticks", num_ticks),
+        TempoEventKind::Combo {
+            combo_multiplier,
+            miss_multiplier,
+        } => format!("combo x{}, miss x{}", combo_multiplier, miss_multiplier),
+        TempoEventKind::Speed {
+            ratio,
+            delay,
+            delay_is_time,
+        } => format!(
+            "speed {}x over {}{}",
+            ratio,
+            delay,
+            if *delay_is_time { "s" } else { " beats" }
+        ),
+        TempoEventKind::Scroll { ratio } => format!("scroll {}x", ratio),
+        TempoEventKind::FakeSegment { num_fake_rows } => format!("fake {} rows", num_fake_rows),
+        TempoEventKind::Label { message } => {
+            format!("label {:?}", crate::label::decode_label(message).0)
+        }
+    }
+}
+
+/// Compares two events of the same type, allowing floating point values to differ by at most
+/// `tolerance`
+fn values_match(a: &TempoEventKind, b: &TempoEventKind, tolerance: f64) -> bool {
+    let close = |a: f64, b: f64| a == b || (a - b).abs() <= tolerance;
+    match (a, b) {
+        (TempoEventKind::Bpm { bpm: a }, TempoEventKind::Bpm { bpm: b }) => close(*a, *b),
+        (TempoEventKind::Stop { time: a }, TempoEventKind::Stop { time: b })
+        | (TempoEventKind::Delay { time: a }, TempoEventKind::Delay { time: b })
+        | (TempoEventKind::Scroll { ratio: a }, TempoEventKind::Scroll { ratio: b }) => {
+            close(*a, *b)
+        }
+        (
+            TempoEventKind::Speed {
+                ratio: ratio_a,
+                delay: delay_a,
+                delay_is_time: delay_is_time_a,
+            },
+            TempoEventKind::Speed {
+                ratio: ratio_b,
+                delay: delay_b,
+                delay_is_time: delay_is_time_b,
+            },
+        ) => {
+            close(*ratio_a, *ratio_b)
+                && close(*delay_a, *delay_b)
+                && delay_is_time_a == delay_is_time_b
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn by_type_and_row(events: &[TempoEvent]) -> BTreeMap<(u8, Row), &TempoEvent> {
+    events
+        .iter()
+        .map(|event| ((tempo_event_kind(&event.kind), event.row), event))
+        .collect()
+}
+
+/// Compares two tempo event lists, for example from two sync attempts
+///
+/// Events are matched by type and row. Floating point values (BPM, stop and delay durations, speed
+/// and scroll ratios and speed delays) that differ by at most `tolerance` are considered equal.
+///
+/// ```rust
+/// use arrowvortex_clipboard::{set_ops, Row, TempoEvent, TempoEventKind};
+///
+/// let old = [
+///     TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 150.0 } },
+///     TempoEvent { row: Row(192), kind: TempoEventKind::Stop { time: 0.5 } },
+/// ];
+/// let new = [
+///     TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 150.0001 } },
+///     TempoEvent { row: Row(192), kind: TempoEventKind::Stop { time: 0.25 } },
+///     TempoEvent { row: Row(384), kind: TempoEventKind::Bpm { bpm: 75.0 } },
+/// ];
+///
+/// let diff = set_ops::diff_tempo(&old, &new, 0.001);
+/// assert_eq!(diff.to_string(), "~ row 192: stop 0.5s -> stop 0.25s\n+ row 384: BPM 75\n");
+/// ```
+pub fn diff_tempo(old: &[TempoEvent], new: &[TempoEvent], tolerance: f64) -> TempoDiff {
+    let (old, new) = (by_type_and_row(old), by_type_and_row(new));
+    let mut diff = TempoDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+    };
+    for (key, &old_event) in &old {
+        match new.get(key) {
+            None => diff.removed.push(old_event.clone()),
+            Some(&new_event) if !values_match(&old_event.kind, &new_event.kind, tolerance) => {
+                diff.changed.push(TempoChange {
+                    old: old_event.clone(),
+                    new: new_event.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, &new_event) in &new {
+        if !old.contains_key(key) {
+            diff.added.push(new_event.clone());
+        }
+    }
+    diff
+}
+
+/// Combines two tempo event lists
+///
+/// Events that match by type, row and value within `tolerance` appear once, taken from `first`.
+/// Events of the same type and row with different values are resolved by `policy`. The result is
+/// sorted by type and row, ready for [`encode_tempo`](crate::encode_tempo).
+pub fn merge_tempo(
+    first: &[TempoEvent],
+    second: &[TempoEvent],
+    tolerance: f64,
+    policy: ConflictPolicy,
+) -> Result<Vec<TempoEvent>, TempoConflict> {
+    let mut events = by_type_and_row(first);
+    for event in second {
+        let key = (tempo_event_kind(&event.kind), event.row);
+        match events.get(&key) {
+            Some(&existing) if !values_match(&existing.kind, &event.kind, tolerance) => {
+                match policy {
+                    ConflictPolicy::KeepFirst => {}
+                    ConflictPolicy::KeepSecond => {
+                        events.insert(key, event);
+                    }
+                    ConflictPolicy::Reject => {
+                        return Err(TempoConflict {
+                            first: existing.clone(),
+                            second: event.clone(),
+                        })
+                    }
+                }
+            }
+            Some(_) => {}
+            None => {
+                events.insert(key, event);
+            }
+        }
+    }
+    Ok(events.into_iter().map(|(_, event)| event.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::parse_notes;
+
+    #[test]
+    fn test_set_ops() {
+        let first = parse_notes("0 1000\n12 0100\n24 0010").unwrap();
+        let second = parse_notes("0 1000\n12 0M00\n48 0001").unwrap();
+
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::KeepFirst).unwrap(),
+            parse_notes("0 1000\n12 0100\n24 0010\n48 0001").unwrap()
+        );
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::KeepSecond).unwrap(),
+            parse_notes("0 1000\n12 0M00\n24 0010\n48 0001").unwrap()
+        );
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::Reject),
+            Err(NoteConflict {
+                first: first[1].clone(),
+                second: second[1].clone(),
+            })
+        );
+
+        assert_eq!(
+            intersection(&first, &second),
+            parse_notes("0 1000").unwrap()
+        );
+        assert_eq!(
+            difference(&first, &second),
+            parse_notes("12 0100\n24 0010").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_union_holds() {
+        let first = parse_notes("0 2000\n12 0100\n24 3000").unwrap();
+        let second = parse_notes("12 1000\n24 1100").unwrap();
+
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::KeepFirst).unwrap(),
+            parse_notes("0 2000\n12 0100\n24 3100").unwrap()
+        );
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::KeepSecond).unwrap(),
+            parse_notes("12 1100\n24 1100").unwrap()
+        );
+        assert_eq!(
+            union(&first, &second, ConflictPolicy::Reject),
+            Err(NoteConflict {
+                first: first[0].clone(),
+                second: second[0].clone(),
+            })
+        );
+
+        // Later duplicates replace earlier ones within a selection
+        let duplicates = parse_notes("0 1000").unwrap();
+        let mine = parse_notes("0 M000").unwrap();
+        let both = [duplicates[0].clone(), mine[0].clone()];
+        assert_eq!(union(&both, &[], ConflictPolicy::Reject).unwrap(), mine);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = parse_notes("0 2000\n12 0100\n24 3000").unwrap();
+        let new = parse_notes("0 2000\n12 0100\n36 3000").unwrap();
+
+        let changes = diff(&old, &new);
+        assert!(changes.added.is_empty() && changes.removed.is_empty());
+        assert_eq!(
+            changes.changed,
+            [NoteChange {
+                old: old[0].clone(),
+                new: new[0].clone(),
+            }]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_merge_tempo() {
+        let bpm = |row, bpm| TempoEvent {
+            row,
+            kind: TempoEventKind::Bpm { bpm },
+        };
+        let first = [bpm(Row(0), 120.0), bpm(Row(192), 140.0)];
+        let second = [
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Delay { time: 0.1 },
+            },
+            bpm(Row(192), 140.00001),
+            bpm(Row(96), 130.0),
+        ];
+
+        let merged = merge_tempo(&first, &second, 0.001, ConflictPolicy::Reject).unwrap();
+        assert_eq!(
+            merged,
+            [
+                first[0].clone(),
+                second[2].clone(),
+                first[1].clone(),
+                second[0].clone()
+            ]
+        );
+        assert!(crate::encode_tempo(&merged).is_ok());
+
+        let conflict = merge_tempo(&first, &second, 0.0, ConflictPolicy::Reject).unwrap_err();
+        assert_eq!(conflict.second, second[1]);
+        assert!(diff_tempo(&first, &first, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+/*!
+[`proptest`] strategies for property tests (requires the `proptest` feature)
+
+Like the [`fuzzing`](crate::fuzzing) support, all strategies generate valid values that survive an
+encoding roundtrip. The list strategies sort and deduplicate their output the way the encoder
+expects.
+
+```rust
+use arrowvortex_clipboard::{strategies, DecodeResult};
+use proptest::test_runner::TestRunner;
+
+TestRunner::default()
+    .run(&strategies::row_based_notes(32), |notes| {
+        let encoded = arrowvortex_clipboard::encode_row_based_notes(&notes).unwrap();
+        match arrowvortex_clipboard::decode(encoded.as_bytes()).unwrap() {
+            DecodeResult::RowBasedNotes(decoded) => assert_eq!(decoded, notes),
+            _ => unreachable!(),
+        }
+        Ok(())
+    })
+    .unwrap();
+```
+*/
+
+use crate::encode::tempo_event_kind;
+use crate::{Note, NoteKind, Row, TempoEvent, TempoEventKind};
+use alloc::{vec, vec::Vec};
+use proptest::prelude::*;
+
+/// Note kinds with the given strategy for the end position of holds and rolls
+fn note_kind<P: core::fmt::Debug + Clone + 'static>(
+    end_pos: impl Strategy<Value = P> + Clone + 'static,
+) -> impl Strategy<Value = NoteKind<P>> {
+    prop_oneof![
+        Just(NoteKind::Tap),
+        end_pos
+            .clone()
+            .prop_map(|end_pos| NoteKind::Hold { end_pos }),
+        Just(NoteKind::Mine),
+        end_pos.prop_map(|end_pos| NoteKind::Roll { end_pos }),
+        Just(NoteKind::Lift),
+        Just(NoteKind::Fake),
+    ]
+}
+
+/// Row-based notes anywhere in the chart, up to [`Row::MAX`], in columns 0 to 127
+pub fn row_based_note() -> impl Strategy<Value = Note<Row>> {
+    (0..Row::MAX.0, 0..128_u8).prop_flat_map(|(pos, column)| {
+        let end_pos =
+            (1..=u16::MAX as u64).prop_map(move |length| Row((pos + length).min(Row::MAX.0)));
+        note_kind(end_pos).prop_map(move |kind| Note {
+            pos: Row(pos),
+            column,
+            kind,
+        })
+    })
+}
+
+/// Time-based notes between -1000 and 100000 seconds, in columns 0 to 127
+pub fn time_based_note() -> impl Strategy<Value = Note<f64>> {
+    (-1000.0..100000.0_f64, 0..128_u8).prop_flat_map(|(pos, column)| {
+        let end_pos = (0.001..100.0_f64).prop_map(move |length| pos + length);
+        note_kind(end_pos).prop_map(move |kind| Note { pos, column, kind })
+    })
+}
+
+/// Finite floating point values
+fn finite_f64() -> impl Strategy<Value = f64> {
+    prop::num::f64::NORMAL | prop::num::f64::ZERO | prop::num::f64::SUBNORMAL
+}
+
+/// Tempo events of all types, with finite floating point values
+pub fn tempo_event() -> impl Strategy<Value = TempoEvent> {
+    let kind = prop_oneof![
+        finite_f64().prop_map(|bpm| TempoEventKind::Bpm { bpm }),
+        finite_f64().prop_map(|time| TempoEventKind::Stop { time }),
+        finite_f64().prop_map(|time| TempoEventKind::Delay { time }),
+        any::<u32>().prop_map(|num_skipped_rows| TempoEventKind::Warp { num_skipped_rows }),
+        (any::<u32>(), any::<u32>()).prop_map(|(numerator, denominator)| {
+            TempoEventKind::TimeSignature {
+                numerator,
+                denominator,
+            }
+        }),
+        any::<u32>().prop_map(|num_ticks| TempoEventKind::Ticks { num_ticks }),
+        (any::<u32>(), any::<u32>()).prop_map(|(combo_multiplier, miss_multiplier)| {
+            TempoEventKind::Combo {
+                combo_multiplier,
+                miss_multiplier,
+            }
+        }),
+        (finite_f64(), finite_f64(), any::<bool>()).prop_map(|(ratio, delay, delay_is_time)| {
+            TempoEventKind::Speed {
+                ratio,
+                delay,
+                delay_is_time,
+            }
+        }),
+        finite_f64().prop_map(|ratio| TempoEventKind::Scroll { ratio }),
+        any::<u32>().prop_map(|num_fake_rows| TempoEventKind::FakeSegment { num_fake_rows }),
+        prop::collection::vec(any::<u8>(), 0..64)
+            .prop_map(|message| TempoEventKind::Label { message }),
+    ];
+    (any::<u32>(), kind).prop_map(|(row, kind)| TempoEvent {
+        row: Row::from(row),
+        kind,
+    })
+}
+
+fn sorted_notes<P: PartialOrd + Copy>(mut notes: Vec<Note<P>>) -> Vec<Note<P>> {
+    notes.sort_by(|a, b| {
+        (a.pos, a.column)
+            .partial_cmp(&(b.pos, b.column))
+            .unwrap_or(core::cmp::Ordering::Equal)
+    });
+    notes.dedup_by(|a, b| (a.pos, a.column) == (b.pos, b.column));
+    notes
+}
+
+/// Lists of up to `max_len` row-based notes, sorted by row and column
+pub fn row_based_notes(max_len: usize) -> impl Strategy<Value = Vec<Note<Row>>> {
+    prop::collection::vec(row_based_note(), 0..=max_len).prop_map(sorted_notes)
+}
+
+/// Lists of up to `max_len` time-based notes, sorted by time and column
+pub fn time_based_notes(max_len: usize) -> impl Strategy<Value = Vec<Note<f64>>> {
+    prop::collection::vec(time_based_note(), 0..=max_len).prop_map(sorted_notes)
+}
+
+/// Lists of up to `max_len` tempo events, sorted by type and row
+pub fn tempo_events(max_len: usize) -> impl Strategy<Value = Vec<TempoEvent>> {
+    prop::collection::vec(tempo_event(), 0..=max_len).prop_map(|mut tempo_events| {
+        tempo_events.sort_by_key(|event| (tempo_event_kind(&event.kind), event.row));
+        tempo_events.dedup_by_key(|event| (tempo_event_kind(&event.kind), event.row));
+        tempo_events
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, DecodeResult};
+
+    proptest! {
+        #[test]
+        fn test_row_based_roundtrip(notes in row_based_notes(64)) {
+            let encoded = crate::encode_row_based_notes(&notes).unwrap();
+            match decode(encoded.as_bytes()).unwrap() {
+                DecodeResult::RowBasedNotes(decoded) => prop_assert_eq!(decoded, notes),
+                _ => prop_assert!(false, "expected row-based notes"),
+            }
+        }
+
+        #[test]
+        fn test_time_based_roundtrip(notes in time_based_notes(64)) {
+            let encoded = crate::encode_time_based_notes(&notes).unwrap();
+            match decode(encoded.as_bytes()).unwrap() {
+                DecodeResult::TimeBasedNotes(decoded) => prop_assert_eq!(decoded, notes),
+                _ => prop_assert!(false, "expected time-based notes"),
+            }
+        }
+
+        #[test]
+        fn test_tempo_roundtrip(tempo_events in tempo_events(64)) {
+            let encoded = crate::encode_tempo(&tempo_events).unwrap();
+            match decode(encoded.as_bytes()).unwrap() {
+                DecodeResult::TempoEvents(decoded) => prop_assert_eq!(decoded, tempo_events),
+                _ => prop_assert!(false, "expected tempo events"),
+            }
+        }
+
+        #[test]
+        fn test_decode_does_not_panic(
+            is_tempo in any::<bool>(),
+            data in prop::collection::vec(33..=117_u8, 0..256),
+        ) {
+            let mut input = if is_tempo {
+                b"ArrowVortex:tempo:".to_vec()
+            } else {
+                b"ArrowVortex:notes:".to_vec()
+            };
+            input.extend(data);
+            let _ = decode(&input);
+        }
+    }
+}
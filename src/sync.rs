@@ -0,0 +1,245 @@
+/*!
+Fitting tempo events to beat timestamps, for syncing songs by tapping along
+
+[`fit_bpm`] takes the time of every beat, for example from tapping along to the song or from an
+onset detector, and finds a piecewise-constant BPM map that explains them. Optionally, pauses
+between beats are turned into stops instead of short BPM changes.
+
+```rust
+use arrowvortex_clipboard::{sync, timing::Timing, Row};
+
+// Four beats at 120 BPM, then four at 150 BPM, starting 0.3 seconds into the song
+let beats = [0.3, 0.8, 1.3, 1.8, 2.2, 2.6, 3.0, 3.4];
+let result = sync::fit_bpm(&beats, &sync::SyncOptions::default()).unwrap();
+
+assert!((result.offset - 0.3).abs() < 1e-9);
+let timing = Timing::new(&result.tempo_events);
+assert!((timing.bpm_at(Row(0)) - 120.0).abs() < 1e-6);
+assert!((timing.bpm_at(Row::from_beat(4)) - 150.0).abs() < 1e-6);
+
+let clipboard_data = arrowvortex_clipboard::encode_tempo(&result.tempo_events)?;
+# Ok::<(), arrowvortex_clipboard::EncodeError>(())
+```
+*/
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::math::Float;
+use crate::{Row, TempoEvent, TempoEventKind};
+use alloc::{vec, vec::Vec};
+
+/// Settings for [`fit_bpm`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncOptions {
+    /// How much a beat interval may deviate from the current beat length, relative to it, before
+    /// it counts as a tempo change
+    pub tolerance: f64,
+    /// Whether a single overlong interval between two regular ones becomes a stop. If false, it
+    /// becomes a BPM change lasting one beat
+    pub detect_stops: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.04,
+            detect_stops: true,
+        }
+    }
+}
+
+/// Result of [`fit_bpm`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncResult {
+    /// Time of the first beat (row 0) in seconds
+    pub offset: f64,
+    /// BPM changes and stops, sorted by type and row, ready for
+    /// [`encode_tempo`](crate::encode_tempo)
+    pub tempo_events: Vec<TempoEvent>,
+}
+
+/// Section of consecutive beats with the same beat length
+struct Segment {
+    /// Index of the segment's first beat
+    first_beat: usize,
+    /// Time of each beat in this segment, with the stops inside the segment subtracted
+    times: Vec<f64>,
+}
+
+impl Segment {
+    fn average_interval(&self) -> f64 {
+        (self.times[self.times.len() - 1] - self.times[0]) / (self.times.len() - 1) as f64
+    }
+
+    /// Least squares fit of the beat length
+    fn fitted_interval(&self) -> f64 {
+        let n = self.times.len() as f64;
+        let mean_beat = (n - 1.0) / 2.0;
+        let mean_time = self.times.iter().sum::<f64>() / n;
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for (beat, &time) in self.times.iter().enumerate() {
+            covariance += (beat as f64 - mean_beat) * (time - mean_time);
+            variance += (beat as f64 - mean_beat).powi(2);
+        }
+        covariance / variance
+    }
+
+    /// Fitted time of the segment's first beat
+    fn fitted_start(&self) -> f64 {
+        let n = self.times.len() as f64;
+        let mean_time = self.times.iter().sum::<f64>() / n;
+        mean_time - self.fitted_interval() * (n - 1.0) / 2.0
+    }
+}
+
+/// Fits BPM changes (and optionally stops) to the given beat timestamps in seconds
+///
+/// Every timestamp is one beat. Timestamps are sorted before fitting. Returns `None` if there are
+/// fewer than two distinct timestamps.
+pub fn fit_bpm(timestamps: &[f64], options: &SyncOptions) -> Option<SyncResult> {
+    let mut times = timestamps
+        .iter()
+        .copied()
+        .filter(|time| time.is_finite())
+        .collect::<Vec<_>>();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    times.dedup();
+    if times.len() < 2 {
+        return None;
+    }
+
+    let is_close = |interval: f64, reference: f64| {
+        (interval - reference).abs() <= options.tolerance * reference
+    };
+    let mut segments = vec![Segment {
+        first_beat: 0,
+        times: vec![times[0], times[1]],
+    }];
+    // Beat index and duration of every stop
+    let mut stops = Vec::new();
+    let mut stopped_time = 0.0;
+    for i in 1..times.len() - 1 {
+        let interval = times[i + 1] - times[i];
+        let segment = segments.last_mut().unwrap();
+        let reference = segment.average_interval();
+
+        if is_close(interval, reference) {
+            segment.times.push(times[i + 1] - stopped_time);
+            continue;
+        }
+
+        let next_interval = times.get(i + 2).map(|&next| next - times[i + 1]);
+        let is_stop = options.detect_stops
+            && interval > reference
+            && next_interval.map_or(false, |next_interval| is_close(next_interval, reference));
+        if is_stop {
+            let duration = interval - reference;
+            stops.push((i, duration));
+            stopped_time += duration;
+            segment.times.push(times[i + 1] - stopped_time);
+        } else {
+            segments.push(Segment {
+                first_beat: i,
+                times: vec![times[i] - stopped_time, times[i + 1] - stopped_time],
+            });
+        }
+    }
+
+    let row = |beat: usize| Row::from_beat(beat as u64);
+    let mut tempo_events = segments
+        .iter()
+        .map(|segment| TempoEvent {
+            row: row(segment.first_beat),
+            kind: TempoEventKind::Bpm {
+                bpm: 60.0 / segment.fitted_interval(),
+            },
+        })
+        .collect::<Vec<_>>();
+    tempo_events.extend(stops.into_iter().map(|(beat, duration)| TempoEvent {
+        row: row(beat),
+        kind: TempoEventKind::Stop { time: duration },
+    }));
+
+    Some(SyncResult {
+        offset: segments[0].fitted_start(),
+        tempo_events,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::Timing;
+
+    /// Deterministic jitter of up to 4ms, like a decent human tapper
+    fn jitter(i: usize) -> f64 {
+        ((i * 7919) % 9) as f64 / 1000.0 - 0.004
+    }
+
+    fn assert_explains(result: &SyncResult, beats: &[f64], max_error: f64) {
+        let timing = Timing::new(&result.tempo_events);
+        for (i, &beat) in beats.iter().enumerate() {
+            let time = result.offset + timing.row_to_time(Row::from_beat(i as u64));
+            assert!(
+                (time - beat).abs() < max_error,
+                "beat {}: {} vs {}",
+                i,
+                time,
+                beat
+            );
+        }
+    }
+
+    #[test]
+    fn test_constant_bpm() {
+        let beats = (0..64)
+            .map(|i| 1.25 + i as f64 * 0.4 + jitter(i))
+            .collect::<Vec<_>>();
+        let result = fit_bpm(&beats, &SyncOptions::default()).unwrap();
+
+        assert_eq!(result.tempo_events.len(), 1);
+        match result.tempo_events[0].kind {
+            TempoEventKind::Bpm { bpm } => assert!((bpm - 150.0).abs() < 0.1),
+            _ => panic!("expected BPM event"),
+        }
+        assert!((result.offset - 1.25).abs() < 0.005);
+        assert_explains(&result, &beats, 0.01);
+    }
+
+    #[test]
+    fn test_bpm_change_and_stop() {
+        // 16 beats at 120 BPM, a 0.3 second stop after beat 7, then 16 beats at 180 BPM
+        let mut beats = Vec::new();
+        let mut time = 0.0;
+        for i in 0..32 {
+            beats.push(time + jitter(i));
+            time += if i < 15 { 0.5 } else { 1.0 / 3.0 };
+            if i == 7 {
+                time += 0.3;
+            }
+        }
+
+        let result = fit_bpm(&beats, &SyncOptions::default()).unwrap();
+        let summary = result
+            .tempo_events
+            .iter()
+            .map(|event| match event.kind {
+                TempoEventKind::Bpm { bpm } => (event.row.0, bpm.round()),
+                TempoEventKind::Stop { time } => (event.row.0, (time * 10.0).round() / 10.0),
+                _ => panic!("unexpected event"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [(0, 120.0), (15 * 48, 180.0), (7 * 48, 0.3)]);
+        assert_explains(&result, &beats, 0.015);
+
+        let without_stops = SyncOptions {
+            detect_stops: false,
+            ..Default::default()
+        };
+        let result = fit_bpm(&beats, &without_stops).unwrap();
+        assert!(result
+            .tempo_events
+            .iter()
+            .all(|event| matches!(event.kind, TempoEventKind::Bpm { .. })));
+    }
+}
@@ -0,0 +1,435 @@
+/*!
+Compact, human-readable text notation for row-based notes
+
+Every line holds one row: the row number, followed by one character per column. The characters
+are the same ones StepMania uses in its .sm files:
+
+| Character | Meaning |
+|-----------|---------|
+| `0` | Empty |
+| `1` | Tap |
+| `2` | Hold head |
+| `4` | Roll head |
+| `3` | Hold or roll tail |
+| `M` | Mine |
+| `L` | Lift |
+| `F` | Fake |
+
+Rows without any notes or tails are left out. Empty lines and lines starting with `//` are ignored
+by the parser.
+
+```rust
+use arrowvortex_clipboard::{text, Note, NoteKind, Row};
+
+let notes = text::parse_notes("
+    // a hold with a jump on top
+    0 2000
+    12 0110
+    24 3001
+")?;
+
+assert_eq!(notes, [
+    Note { pos: Row(0), column: 0, kind: NoteKind::Hold { end_pos: Row(24) } },
+    Note { pos: Row(12), column: 1, kind: NoteKind::Tap },
+    Note { pos: Row(12), column: 2, kind: NoteKind::Tap },
+    Note { pos: Row(24), column: 3, kind: NoteKind::Tap },
+]);
+assert_eq!(text::format_notes(&notes, 4)?, "0 2000\n12 0110\n24 3001\n");
+# Ok::<(), arrowvortex_clipboard::text::TextError>(())
+```
+*/
+
+use crate::{Note, NoteKind, Row};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+/// Error in [`parse_notes`] or [`format_notes`] call
+///
+/// All line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextError {
+    /// Line is not made up of a row number and a column string
+    InvalidLine {
+        /// Line number
+        line: usize,
+    },
+    /// Row number is not larger than the previous line's row number
+    RowNotAscending {
+        /// Line number
+        line: usize,
+    },
+    /// Column string contained a character that doesn't stand for any note
+    UnknownNoteChar {
+        /// Line number
+        line: usize,
+        /// The unknown character
+        character: char,
+    },
+    /// Column string is longer than the 128 columns that ArrowVortex clipboard data can hold
+    TooManyColumns {
+        /// Line number
+        line: usize,
+    },
+    /// A hold or roll tail appeared on a column without a hold or roll
+    UnmatchedTail {
+        /// Line number
+        line: usize,
+        /// Column of the tail
+        column: u8,
+    },
+    /// A note appeared on a column while a hold or roll was still active on it
+    NoteInsideHold {
+        /// Line number
+        line: usize,
+        /// Column of the note
+        column: u8,
+    },
+    /// Input ended while a hold or roll was still active
+    UnterminatedHold {
+        /// Column of the hold or roll
+        column: u8,
+    },
+    /// A hold or roll passed to [`format_notes`] ends at or before its head, so its tail would
+    /// overwrite the head or come before it
+    InvalidHoldLength {
+        /// Row of the hold or roll head
+        row: Row,
+        /// Column of the hold or roll
+        column: u8,
+    },
+    /// A note passed to [`format_notes`] shares its row and column with another note, or lies
+    /// inside or on the tail of a hold or roll
+    OverlappingNotes {
+        /// Row of the later note
+        row: Row,
+        /// Column of the notes
+        column: u8,
+    },
+    /// A note passed to [`format_notes`] is beyond the 128 columns that ArrowVortex clipboard
+    /// data can hold
+    InvalidColumn {
+        /// Row of the note
+        row: Row,
+        /// Column of the note
+        column: u8,
+    },
+}
+
+impl core::fmt::Display for TextError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidLine { line } => write!(f, "line {}: expected row and columns", line),
+            Self::RowNotAscending { line } => write!(f, "line {}: rows must be ascending", line),
+            Self::UnknownNoteChar { line, character } => {
+                write!(f, "line {}: unknown note character {:?}", line, character)
+            }
+            Self::TooManyColumns { line } => write!(f, "line {}: too many columns", line),
+            Self::UnmatchedTail { line, column } => {
+                write!(f, "line {}: tail without hold in column {}", line, column)
+            }
+            Self::NoteInsideHold { line, column } => {
+                write!(f, "line {}: note inside hold in column {}", line, column)
+            }
+            Self::UnterminatedHold { column } => {
+                write!(f, "hold in column {} is never terminated", column)
+            }
+            Self::InvalidHoldLength { row, column } => write!(
+                f,
+                "hold at row {} in column {} does not end after its head",
+                row.0, column
+            ),
+            Self::OverlappingNotes { row, column } => write!(
+                f,
+                "note at row {} in column {} overlaps another note",
+                row.0, column
+            ),
+            Self::InvalidColumn { row, column } => write!(
+                f,
+                "note at row {} is in column {}, beyond the last column 127",
+                row.0, column
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TextError {}
+
+/// Writes `notes` in text notation, one line per row
+///
+/// `num_columns` is the minimum number of columns in each line; lines are widened as needed to fit
+/// every note.
+///
+/// Notes that couldn't be parsed back from the text are rejected: holds and rolls that don't end
+/// after their head fail with [`TextError::InvalidHoldLength`], notes on the same row and column
+/// or inside a hold or roll with [`TextError::OverlappingNotes`], and notes in column 128 or
+/// above with [`TextError::InvalidColumn`].
+pub fn format_notes(notes: &[Note<Row>], num_columns: u8) -> Result<String, TextError> {
+    // Sorted by column and row, so that every note only has to be checked against the previous one
+    let mut sorted_notes = notes.iter().collect::<Vec<_>>();
+    sorted_notes.sort_by_key(|note| (note.column, note.pos));
+    // Column and last occupied row of the previous note, including its tail
+    let mut prev_note: Option<(u8, Row)> = None;
+    for note in sorted_notes {
+        if note.column >= 128 {
+            return Err(TextError::InvalidColumn {
+                row: note.pos,
+                column: note.column,
+            });
+        }
+        let end_pos = match note.kind {
+            NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } if end_pos <= note.pos => {
+                return Err(TextError::InvalidHoldLength {
+                    row: note.pos,
+                    column: note.column,
+                });
+            }
+            NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } => end_pos,
+            _ => note.pos,
+        };
+        if let Some((column, occupied_until)) = prev_note {
+            if column == note.column && note.pos <= occupied_until {
+                return Err(TextError::OverlappingNotes {
+                    row: note.pos,
+                    column: note.column,
+                });
+            }
+        }
+        prev_note = Some((note.column, end_pos));
+    }
+
+    let num_columns = notes
+        .iter()
+        .map(|note| note.column as usize + 1)
+        .max()
+        .unwrap_or(0)
+        .max(num_columns as usize);
+
+    let mut rows = alloc::collections::BTreeMap::new();
+    let mut set_cell = |row: u64, column: u8, character: u8| {
+        rows.entry(row).or_insert_with(|| vec![b'0'; num_columns])[column as usize] = character;
+    };
+    for note in notes {
+        let character = match note.kind {
+            NoteKind::Tap => b'1',
+            NoteKind::Hold { .. } => b'2',
+            NoteKind::Mine => b'M',
+            NoteKind::Roll { .. } => b'4',
+            NoteKind::Lift => b'L',
+            NoteKind::Fake => b'F',
+        };
+        set_cell(note.pos.0, note.column, character);
+        if let NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } = note.kind {
+            set_cell(end_pos.0, note.column, b'3');
+        }
+    }
+
+    let mut output = String::new();
+    for (row, cells) in rows {
+        output += &row.to_string();
+        output.push(' ');
+        // Only ASCII characters were written into the cells
+        output += core::str::from_utf8(&cells).unwrap();
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Parses text notation into a list of notes, sorted by row and column
+///
+/// See the [module documentation](self) for the format.
+pub fn parse_notes(text: &str) -> Result<Vec<Note<Row>>, TextError> {
+    let mut notes: Vec<Note<Row>> = Vec::new();
+    // Index into `notes` of the hold or roll that is currently active in each column
+    let mut active_holds: Vec<Option<usize>> = Vec::new();
+    let mut prev_row = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        let (row, cells) = match (tokens.next(), tokens.next(), tokens.next()) {
+            (Some(row), Some(cells), None) => (row, cells),
+            _ => return Err(TextError::InvalidLine { line: line_number }),
+        };
+        let row = row
+            .parse::<u64>()
+            .map(Row)
+            .map_err(|_| TextError::InvalidLine { line: line_number })?;
+        if prev_row.map_or(false, |prev_row| row <= prev_row) {
+            return Err(TextError::RowNotAscending { line: line_number });
+        }
+        prev_row = Some(row);
+
+        for (column, character) in cells.chars().enumerate() {
+            // The encoder uses the top bit of the column byte for other purposes
+            if column >= 128 {
+                return Err(TextError::TooManyColumns { line: line_number });
+            }
+            if active_holds.len() <= column {
+                active_holds.resize(column + 1, None);
+            }
+            let active_hold = &mut active_holds[column];
+            let column = column as u8;
+
+            let kind = match character {
+                '0' => continue,
+                '3' => match active_hold.take() {
+                    Some(hold_index) => {
+                        if let NoteKind::Hold { end_pos } | NoteKind::Roll { end_pos } =
+                            &mut notes[hold_index].kind
+                        {
+                            *end_pos = row;
+                        }
+                        continue;
+                    }
+                    None => {
+                        return Err(TextError::UnmatchedTail {
+                            line: line_number,
+                            column,
+                        })
+                    }
+                },
+                '1' => NoteKind::Tap,
+                '2' => NoteKind::Hold { end_pos: row },
+                '4' => NoteKind::Roll { end_pos: row },
+                'M' => NoteKind::Mine,
+                'L' => NoteKind::Lift,
+                'F' => NoteKind::Fake,
+                character => {
+                    return Err(TextError::UnknownNoteChar {
+                        line: line_number,
+                        character,
+                    })
+                }
+            };
+
+            if active_hold.is_some() {
+                return Err(TextError::NoteInsideHold {
+                    line: line_number,
+                    column,
+                });
+            }
+            if let NoteKind::Hold { .. } | NoteKind::Roll { .. } = kind {
+                *active_hold = Some(notes.len());
+            }
+            notes.push(Note {
+                pos: row,
+                column,
+                kind,
+            });
+        }
+    }
+
+    if let Some(column) = active_holds.iter().position(Option::is_some) {
+        return Err(TextError::UnterminatedHold {
+            column: column as u8,
+        });
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "0 2M00\n6 0L40\n12 0F01\n24 3030\n";
+        let notes = parse_notes(text).unwrap();
+        assert_eq!(notes.len(), 6);
+        assert_eq!(format_notes(&notes, 4).unwrap(), text);
+
+        let zero_length_hold = Note {
+            pos: Row(12),
+            column: 1,
+            kind: NoteKind::Hold { end_pos: Row(12) },
+        };
+        assert_eq!(
+            format_notes(&[zero_length_hold], 4),
+            Err(TextError::InvalidHoldLength {
+                row: Row(12),
+                column: 1
+            })
+        );
+
+        let encoded = crate::encode_row_based_notes(&notes).unwrap();
+        match crate::decode(encoded.as_bytes()).unwrap() {
+            crate::DecodeResult::RowBasedNotes(decoded) => assert_eq!(decoded, notes),
+            _ => panic!("Unexpected data type"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_notes("0 1000\n0 0100"),
+            Err(TextError::RowNotAscending { line: 2 })
+        );
+        assert_eq!(
+            parse_notes("0 2000\n12 1000"),
+            Err(TextError::NoteInsideHold { line: 2, column: 0 })
+        );
+        assert_eq!(
+            parse_notes("0 0300"),
+            Err(TextError::UnmatchedTail { line: 1, column: 1 })
+        );
+        assert_eq!(
+            parse_notes("0 0040"),
+            Err(TextError::UnterminatedHold { column: 2 })
+        );
+        assert_eq!(
+            parse_notes("0 10X0"),
+            Err(TextError::UnknownNoteChar {
+                line: 1,
+                character: 'X'
+            })
+        );
+        assert_eq!(
+            parse_notes(&format!("0 {}1", "0".repeat(128))),
+            Err(TextError::TooManyColumns { line: 1 })
+        );
+        assert!(parse_notes(&format!("0 {}1", "0".repeat(127))).is_ok());
+        assert_eq!(
+            parse_notes("zero 1000"),
+            Err(TextError::InvalidLine { line: 1 })
+        );
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let note = |pos, column, kind| Note {
+            pos: Row(pos),
+            column,
+            kind,
+        };
+        let hold = || NoteKind::Hold { end_pos: Row(24) };
+        let overlap = |row, column| Err(TextError::OverlappingNotes { row, column });
+
+        let duplicate = [note(12, 1, NoteKind::Tap), note(12, 1, NoteKind::Mine)];
+        assert_eq!(format_notes(&duplicate, 4), overlap(Row(12), 1));
+        let inside_hold = [note(0, 2, hold()), note(12, 2, NoteKind::Tap)];
+        assert_eq!(format_notes(&inside_hold, 4), overlap(Row(12), 2));
+        let on_tail = [note(24, 0, NoteKind::Tap), note(0, 0, hold())];
+        assert_eq!(format_notes(&on_tail, 4), overlap(Row(24), 0));
+        let after_tail = [note(0, 0, hold()), note(36, 0, NoteKind::Tap)];
+        assert!(format_notes(&after_tail, 4).is_ok());
+
+        assert_eq!(
+            format_notes(&[note(0, 128, NoteKind::Tap)], 4),
+            Err(TextError::InvalidColumn {
+                row: Row(0),
+                column: 128
+            })
+        );
+        assert!(format_notes(&[note(0, 127, NoteKind::Tap)], 4).is_ok());
+    }
+}
@@ -0,0 +1,329 @@
+/*!
+Conversion between rows and real time, based on [tempo events](crate::TempoEvent)
+
+```rust
+use arrowvortex_clipboard::{timing::Timing, Row, TempoEvent, TempoEventKind};
+
+let timing = Timing::new(&[
+    TempoEvent { row: Row(0), kind: TempoEventKind::Bpm { bpm: 120.0 } },
+    TempoEvent { row: Row(96), kind: TempoEventKind::Stop { time: 0.5 } },
+]);
+
+assert_eq!(timing.row_to_time(Row(48)), 0.5);
+// The stop happens after the notes on its own row have been hit
+assert_eq!(timing.row_to_time(Row(96)), 1.0);
+assert_eq!(timing.row_to_time(Row(144)), 2.0);
+```
+*/
+
+use crate::{Row, TempoEvent, TempoEventKind, ROWS_PER_BEAT};
+use alloc::{vec, vec::Vec};
+
+/// BPM that is assumed when there are no BPM events
+pub const DEFAULT_BPM: f64 = 120.0;
+
+#[derive(Debug, Clone, PartialEq)]
+struct BpmSegment {
+    row: u64,
+    /// Time at `row`, not counting pauses
+    time: f64,
+    seconds_per_row: f64,
+}
+
+/// Pauses of one type, sorted by row, with the total duration of all pauses up to and including
+/// each one
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Pauses {
+    rows: Vec<u64>,
+    cumulative_seconds: Vec<f64>,
+}
+
+impl Pauses {
+    fn new(mut pauses: Vec<(u64, f64)>) -> Self {
+        pauses.sort_by_key(|&(row, _)| row);
+        let mut total = 0.0;
+        Self {
+            rows: pauses.iter().map(|&(row, _)| row).collect(),
+            cumulative_seconds: pauses
+                .iter()
+                .map(|&(_, seconds)| {
+                    total += seconds;
+                    total
+                })
+                .collect(),
+        }
+    }
+
+    /// Sums up all pauses whose row satisfies `is_before`
+    fn total_before(&self, is_before: impl Fn(u64) -> bool) -> f64 {
+        let count = self.rows.iter().take_while(|&&row| is_before(row)).count();
+        match count {
+            0 => 0.0,
+            count => self.cumulative_seconds[count - 1],
+        }
+    }
+}
+
+/// Maps rows to points in time, taking BPM changes, stops, delays and warps into account
+///
+/// Row 0 is at time 0; ArrowVortex clipboard data doesn't contain the song offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    bpm_segments: Vec<BpmSegment>,
+    stops: Pauses,
+    delays: Pauses,
+    /// Non-overlapping row ranges skipped by warps, sorted by row
+    warps: Vec<core::ops::Range<u64>>,
+}
+
+impl Timing {
+    /// Builds the timing from a list of tempo events, in any order
+    ///
+    /// Events that don't affect timing are ignored, as are BPM events with non-positive or
+    /// non-finite BPM and stops and delays with non-finite times. If there is no BPM event at
+    /// row 0, the first BPM (or [`DEFAULT_BPM`]) is used from the start.
+    pub fn new(tempo_events: &[TempoEvent]) -> Self {
+        let mut bpm_changes = Vec::new();
+        let mut stops = Vec::new();
+        let mut delays = Vec::new();
+        let mut warps = Vec::new();
+        for event in tempo_events {
+            let row = event.row.0;
+            match event.kind {
+                TempoEventKind::Bpm { bpm } if bpm.is_finite() && bpm > 0.0 => {
+                    bpm_changes.push((row, bpm))
+                }
+                TempoEventKind::Stop { time } if time.is_finite() => stops.push((row, time)),
+                TempoEventKind::Delay { time } if time.is_finite() => delays.push((row, time)),
+                TempoEventKind::Warp { num_skipped_rows } if num_skipped_rows > 0 => {
+                    warps.push(row..row.saturating_add(num_skipped_rows as u64))
+                }
+                _ => {}
+            }
+        }
+        bpm_changes.sort_by_key(|&(row, _)| row);
+
+        let seconds_per_row = |bpm: f64| 60.0 / bpm / ROWS_PER_BEAT as f64;
+        let first_bpm = bpm_changes.first().map_or(DEFAULT_BPM, |&(_, bpm)| bpm);
+        let mut bpm_segments = vec![BpmSegment {
+            row: 0,
+            time: 0.0,
+            seconds_per_row: seconds_per_row(first_bpm),
+        }];
+        for (row, bpm) in bpm_changes {
+            let last = bpm_segments.last_mut().unwrap();
+            if row == last.row {
+                // Later events on the same row take precedence
+                last.seconds_per_row = seconds_per_row(bpm);
+                continue;
+            }
+            let time = last.time + (row - last.row) as f64 * last.seconds_per_row;
+            bpm_segments.push(BpmSegment {
+                row,
+                time,
+                seconds_per_row: seconds_per_row(bpm),
+            });
+        }
+
+        warps.sort_by_key(|warp| warp.start);
+        let mut merged_warps: Vec<core::ops::Range<u64>> = Vec::new();
+        for warp in warps {
+            match merged_warps.last_mut() {
+                Some(last) if warp.start <= last.end => last.end = last.end.max(warp.end),
+                _ => merged_warps.push(warp),
+            }
+        }
+
+        Self {
+            bpm_segments,
+            stops: Pauses::new(stops),
+            delays: Pauses::new(delays),
+            warps: merged_warps,
+        }
+    }
+
+    /// Returns the BPM at the given row
+    pub fn bpm_at(&self, row: Row) -> f64 {
+        60.0 / self.bpm_segment(row.0).seconds_per_row / ROWS_PER_BEAT as f64
+    }
+
+    fn bpm_segment(&self, row: u64) -> &BpmSegment {
+        let index = self
+            .bpm_segments
+            .iter()
+            .rposition(|segment| segment.row <= row)
+            .unwrap_or(0);
+        &self.bpm_segments[index]
+    }
+
+    /// Returns whether the given row is skipped by a warp
+    ///
+    /// The row a warp starts on is not skipped, but the rows after it are, up to the row the warp
+    /// lands on. Notes on skipped rows cannot be hit.
+    pub fn is_warped(&self, row: Row) -> bool {
+        let row = row.0;
+        self.warps
+            .iter()
+            .any(|warp| warp.start < row && row < warp.end)
+    }
+
+    /// Time at the given row according to the BPM changes alone
+    fn bpm_time(&self, row: u64) -> f64 {
+        let segment = self.bpm_segment(row);
+        segment.time + (row - segment.row) as f64 * segment.seconds_per_row
+    }
+
+    /// Returns the time in seconds at which the notes on the given row are to be hit
+    ///
+    /// Stops on a row take effect after its notes, delays before. Rows skipped by a warp are at
+    /// the time of the warp's start.
+    pub fn row_to_time(&self, row: Row) -> f64 {
+        self.time_at(row.0)
+    }
+
+    /// [`Timing::row_to_time`] on a raw row number, as searched by [`Timing::time_to_row`]
+    fn time_at(&self, row: u64) -> f64 {
+        let warped_seconds = self
+            .warps
+            .iter()
+            .take_while(|warp| warp.start < row)
+            .map(|warp| self.bpm_time(warp.end.min(row)) - self.bpm_time(warp.start))
+            .sum::<f64>();
+        self.bpm_time(row) - warped_seconds
+            + self.stops.total_before(|stop_row| stop_row < row)
+            + self.delays.total_before(|delay_row| delay_row <= row)
+    }
+
+    /// Returns the row that is reached at the given time in seconds, including the fraction of a
+    /// row that has passed
+    ///
+    /// This is the inverse of [`Timing::row_to_time`]. During a stop or delay, the row stays where
+    /// it is. Times before row 0 are extrapolated using the first BPM.
+    pub fn time_to_row(&self, time: f64) -> f64 {
+        let first_seconds_per_row = self.bpm_segments[0].seconds_per_row;
+        if time < self.time_at(0) {
+            return (time - self.time_at(0)) / first_seconds_per_row;
+        }
+
+        // Find the last row that is reached at or before `time`
+        let mut high = 1;
+        while self.time_at(high) <= time && high < u64::MAX / 2 {
+            high *= 2;
+        }
+        let mut low = 0;
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if self.time_at(middle) <= time {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        // Between two rows, time only passes at the BPM; stops happen right after the first row
+        // and delays right before the second
+        if self.is_warped(Row(low + 1)) {
+            return low as f64;
+        }
+        let stop =
+            self.stops.total_before(|row| row <= low) - self.stops.total_before(|row| row < low);
+        let moving_since = self.time_at(low) + stop;
+        let fraction = (time - moving_since) / self.bpm_segment(low).seconds_per_row;
+        low as f64 + fraction.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_to_time() {
+        let timing = Timing::new(&[
+            TempoEvent {
+                row: Row(192),
+                kind: TempoEventKind::Bpm { bpm: 240.0 },
+            },
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Delay { time: 0.25 },
+            },
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 60.0 },
+            },
+        ]);
+
+        assert_eq!(timing.row_to_time(Row(0)), 0.0);
+        assert_eq!(timing.row_to_time(Row(48)), 1.0);
+        assert_eq!(timing.row_to_time(Row(96)), 2.25);
+        assert_eq!(timing.row_to_time(Row(192)), 4.25);
+        assert_eq!(timing.row_to_time(Row(240)), 4.5);
+        assert_eq!(timing.bpm_at(Row(191)), 60.0);
+        assert_eq!(timing.bpm_at(Row(192)), 240.0);
+    }
+
+    #[test]
+    fn test_warps() {
+        let timing = Timing::new(&[
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 60.0 },
+            },
+            TempoEvent {
+                row: Row(48),
+                kind: TempoEventKind::Warp {
+                    num_skipped_rows: 96,
+                },
+            },
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Warp {
+                    num_skipped_rows: 96,
+                },
+            },
+        ]);
+
+        assert_eq!(timing.row_to_time(Row(48)), 1.0);
+        assert_eq!(timing.row_to_time(Row(120)), 1.0);
+        assert_eq!(timing.row_to_time(Row(192)), 1.0);
+        assert_eq!(timing.row_to_time(Row(240)), 2.0);
+        assert!(!timing.is_warped(Row(48)));
+        assert!(timing.is_warped(Row(100)));
+        assert!(!timing.is_warped(Row(192)));
+        assert_eq!(timing.time_to_row(1.0), 192.0);
+        assert_eq!(timing.time_to_row(1.5), 216.0);
+    }
+
+    #[test]
+    fn test_time_to_row() {
+        let timing = Timing::new(&[
+            TempoEvent {
+                row: Row(0),
+                kind: TempoEventKind::Bpm { bpm: 60.0 },
+            },
+            TempoEvent {
+                row: Row(48),
+                kind: TempoEventKind::Stop { time: 0.5 },
+            },
+            TempoEvent {
+                row: Row(96),
+                kind: TempoEventKind::Delay { time: 0.5 },
+            },
+        ]);
+
+        for &(time, row) in &[
+            (-0.5, -24.0),
+            (0.5, 24.0),
+            (1.25, 48.0),
+            (2.0, 72.0),
+            (2.75, 96.0),
+            (3.5, 120.0),
+        ] {
+            assert!((timing.time_to_row(time) - row).abs() < 1e-9);
+        }
+        for row in 0..200 {
+            assert!((timing.time_to_row(timing.row_to_time(Row(row))) - row as f64).abs() < 1e-9);
+        }
+    }
+}